@@ -0,0 +1,231 @@
+//! Shared setup for the DREGAN program tests: both programs run natively under
+//! solana-program-test, with mints and token accounts added before the bank starts.
+
+use borsh::BorshDeserialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    clock::Clock,
+    instruction::{Instruction, InstructionError},
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::{Keypair, Signer},
+    system_program,
+    transaction::{Transaction, TransactionError},
+};
+use spl_token::state::{Account as TokenAccount, AccountState, Mint};
+use std::{
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+pub const START: i64 = 1_700_000_000;
+pub const DAY: i64 = 24 * 60 * 60;
+
+const TOKEN_METADATA_FIXTURE: &str = "tests/fixtures/mpl_token_metadata.so";
+const DEFAULT_RPC_URL: &str = "https://api.mainnet-beta.solana.com";
+static FIXTURE_FETCHES: AtomicUsize = AtomicUsize::new(0);
+
+/// Both programs as native processors
+pub fn program_test() -> ProgramTest {
+    let mut program_test = ProgramTest::default();
+    program_test.prefer_bpf(false);
+    program_test.add_program(
+        "dregan_staking",
+        dregan_staking::id(),
+        processor!(dregan_staking::process_instruction),
+    );
+    program_test.add_program(
+        "dregan_nft",
+        dregan_nft::id(),
+        processor!(dregan_nft::process_instruction),
+    );
+    program_test
+}
+
+/// Load the Token Metadata program from its fixture, dumping the deployed binary
+/// from `SOLANA_RPC_URL` (mainnet by default) the first time
+pub async fn add_token_metadata_program(program_test: &mut ProgramTest) {
+    let fixture = Path::new(TOKEN_METADATA_FIXTURE);
+    if !fixture.exists() {
+        let url = std::env::var("SOLANA_RPC_URL").unwrap_or_else(|_| DEFAULT_RPC_URL.to_string());
+        let rpc = RpcClient::new(url.clone());
+        let (program_data, _) = Pubkey::find_program_address(
+            &[mpl_token_metadata::ID.as_ref()],
+            &bpf_loader_upgradeable::id(),
+        );
+        let account = rpc
+            .get_account(&program_data)
+            .await
+            .unwrap_or_else(|error| panic!("fetching Token Metadata from {}: {}", url, error));
+        let elf = &account.data[UpgradeableLoaderState::size_of_programdata_metadata()..];
+        // Parallel tests may fetch at once; each writes its own file and renames it into place
+        let partial = fixture.with_extension(format!(
+            "so.{}.{}",
+            std::process::id(),
+            FIXTURE_FETCHES.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(fixture.parent().unwrap()).unwrap();
+        std::fs::write(&partial, elf).unwrap();
+        std::fs::rename(&partial, fixture).unwrap();
+    }
+    program_test.add_program("mpl_token_metadata", mpl_token_metadata::ID, None);
+}
+
+/// A rent-exempt account holding `data`, owned by `owner`
+pub fn account_with_data(data: Vec<u8>, owner: Pubkey) -> Account {
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// A system account holding 10 SOL, to sign and pay as a user
+pub fn add_wallet(program_test: &mut ProgramTest) -> Keypair {
+    let wallet = Keypair::new();
+    program_test.add_account(
+        wallet.pubkey(),
+        Account::new(10_000_000_000, 0, &system_program::id()),
+    );
+    wallet
+}
+
+pub fn add_mint(program_test: &mut ProgramTest, authority: &Pubkey, decimals: u8) -> Pubkey {
+    let mint = Pubkey::new_unique();
+    let mut data = vec![0; Mint::LEN];
+    Mint {
+        mint_authority: COption::Some(*authority),
+        supply: 0,
+        decimals,
+        is_initialized: true,
+        freeze_authority: COption::None,
+    }
+    .pack_into_slice(&mut data);
+    program_test.add_account(mint, account_with_data(data, spl_token::id()));
+    mint
+}
+
+pub fn token_account_data(mint: &Pubkey, owner: &Pubkey, amount: u64) -> Vec<u8> {
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount {
+        mint: *mint,
+        owner: *owner,
+        amount,
+        delegate: COption::None,
+        state: AccountState::Initialized,
+        is_native: COption::None,
+        delegated_amount: 0,
+        close_authority: COption::None,
+    }
+    .pack_into_slice(&mut data);
+    data
+}
+
+pub fn add_token_account(
+    program_test: &mut ProgramTest,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+) -> Pubkey {
+    let address = Pubkey::new_unique();
+    program_test.add_account(
+        address,
+        account_with_data(token_account_data(mint, owner, amount), spl_token::id()),
+    );
+    address
+}
+
+/// Program data naming `upgrade_authority`, as the upgradeable loader would
+/// keep it for a deployed `program_id`
+pub fn add_program_data(
+    program_test: &mut ProgramTest,
+    program_id: &Pubkey,
+    upgrade_authority: &Pubkey,
+) -> Pubkey {
+    let (program_data, _) = Pubkey::find_program_address(
+        &[program_id.as_ref()],
+        &bpf_loader_upgradeable::id(),
+    );
+    let mut data = bincode::serialize(&UpgradeableLoaderState::ProgramData {
+        slot: 0,
+        upgrade_authority_address: Some(*upgrade_authority),
+    })
+    .unwrap();
+    data.resize(UpgradeableLoaderState::size_of_programdata_metadata(), 0);
+    program_test.add_account(program_data, account_with_data(data, bpf_loader_upgradeable::id()));
+    program_data
+}
+
+/// Overwrite a token account's balance in a running bank
+pub fn set_token_balance(
+    context: &mut ProgramTestContext,
+    address: &Pubkey,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+) {
+    let account = account_with_data(token_account_data(mint, owner, amount), spl_token::id());
+    context.set_account(address, &AccountSharedData::from(account));
+}
+
+pub async fn token_balance(context: &mut ProgramTestContext, address: &Pubkey) -> u64 {
+    let account = context
+        .banks_client
+        .get_account(*address)
+        .await
+        .unwrap()
+        .expect("token account exists");
+    TokenAccount::unpack(&account.data).unwrap().amount
+}
+
+/// Deserialize a program account; trailing bytes are not allowed
+pub async fn get_state<T: BorshDeserialize>(context: &mut ProgramTestContext, address: &Pubkey) -> T {
+    let account = context
+        .banks_client
+        .get_account(*address)
+        .await
+        .unwrap()
+        .expect("account exists");
+    T::try_from_slice(&account.data).unwrap()
+}
+
+pub async fn set_unix_timestamp(context: &mut ProgramTestContext, unix_timestamp: i64) {
+    let mut clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp = unix_timestamp;
+    context.set_sysvar(&clock);
+}
+
+/// Send `instructions` as one transaction paid by the context payer. Each call
+/// waits for a fresh blockhash so a repeated transaction is executed again
+/// rather than deduplicated.
+pub async fn process(
+    context: &mut ProgramTestContext,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), BanksClientError> {
+    let blockhash = context.get_new_latest_blockhash().await.unwrap();
+    let mut all_signers = vec![&context.payer];
+    all_signers.extend_from_slice(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&context.payer.pubkey()),
+        &all_signers,
+        blockhash,
+    );
+    context.banks_client.process_transaction(transaction).await
+}
+
+/// The instruction error a transaction failed with
+pub fn instruction_error(result: Result<(), BanksClientError>) -> InstructionError {
+    match result.expect_err("transaction should fail").unwrap() {
+        TransactionError::InstructionError(_, error) => error,
+        error => panic!("unexpected transaction error: {:?}", error),
+    }
+}
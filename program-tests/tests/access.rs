@@ -0,0 +1,440 @@
+//! dregan-nft: the access config PDA, the paths a look-alike config could hijack,
+//! SyncNft for a stale holder and the legacy config migration
+
+use borsh::BorshSerialize;
+use dregan_nft::{
+    AccessAccount, AccessConfig, AccessInstruction, AccessTier, ACCESS_SEED, CONFIG_SEED,
+    DEFAULT_VERIFICATION_VALIDITY, MINT_AUTHORITY_SEED,
+};
+use dregan_program_tests::*;
+use solana_program_test::{ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction, InstructionError},
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::{Keypair, Signer},
+    system_program, sysvar,
+};
+
+const USER_BALANCE: u64 = 30_000;
+
+fn nft_instruction(instruction: AccessInstruction, accounts: Vec<AccountMeta>) -> Instruction {
+    Instruction {
+        program_id: dregan_nft::id(),
+        accounts,
+        data: instruction.try_to_vec().unwrap(),
+    }
+}
+
+fn pda(seeds: &[&[u8]]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(seeds, &dregan_nft::id())
+}
+
+fn config_address() -> Pubkey {
+    pda(&[CONFIG_SEED]).0
+}
+
+fn access_address(owner: &Pubkey) -> Pubkey {
+    pda(&[ACCESS_SEED, owner.as_ref()]).0
+}
+
+struct Access {
+    authority: Keypair,
+    user: Keypair,
+    attacker: Keypair,
+    token_mint: Pubkey,
+    user_token_account: Pubkey,
+    attacker_token_account: Pubkey,
+}
+
+fn add_access_accounts(program_test: &mut ProgramTest) -> Access {
+    let authority = add_wallet(program_test);
+    let user = add_wallet(program_test);
+    let attacker = add_wallet(program_test);
+    let token_mint = add_mint(program_test, &authority.pubkey(), 0);
+    let user_token_account = add_token_account(program_test, &token_mint, &user.pubkey(), USER_BALANCE);
+    let attacker_token_account = add_token_account(program_test, &token_mint, &attacker.pubkey(), 0);
+    Access {
+        authority,
+        user,
+        attacker,
+        token_mint,
+        user_token_account,
+        attacker_token_account,
+    }
+}
+
+/// A program-owned config at an arbitrary address, run by the attacker with a
+/// 1-token Elite threshold and their own treasury
+fn add_look_alike_config(program_test: &mut ProgramTest, access: &Access) -> Pubkey {
+    let mut config = AccessConfig::new(access.attacker.pubkey(), access.token_mint, 0, 255).unwrap();
+    config.thresholds = [1, 1, 1];
+    config.verification_slot_gap = 0;
+    config.subscription_treasury = access.attacker_token_account;
+    config.subscription_prices = [1, 1, 1];
+    let address = Pubkey::new_unique();
+    program_test.add_account(
+        address,
+        account_with_data(config.try_to_vec().unwrap(), dregan_nft::id()),
+    );
+    address
+}
+
+fn initialize_config(access: &Access, config: &Pubkey, bump: u8) -> Instruction {
+    nft_instruction(
+        AccessInstruction::InitializeConfig { bump },
+        vec![
+            AccountMeta::new(*config, false),
+            AccountMeta::new(access.authority.pubkey(), true),
+            AccountMeta::new_readonly(access.token_mint, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+fn verify_access(access: &Access, config: &Pubkey) -> Instruction {
+    let user = access.user.pubkey();
+    nft_instruction(
+        AccessInstruction::VerifyAccess,
+        vec![
+            AccountMeta::new(access_address(&user), false),
+            AccountMeta::new_readonly(user, true),
+            AccountMeta::new_readonly(access.user_token_account, false),
+            AccountMeta::new_readonly(*config, false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+        ],
+    )
+}
+
+fn grant_tier(access: &Access, config: &Pubkey, signer: &Pubkey) -> Instruction {
+    let user = access.user.pubkey();
+    nft_instruction(
+        AccessInstruction::GrantTier {
+            user,
+            tier: AccessTier::Elite,
+            expires_at: START + 365 * DAY,
+        },
+        vec![
+            AccountMeta::new(access_address(&user), false),
+            AccountMeta::new_readonly(*config, false),
+            AccountMeta::new_readonly(*signer, true),
+        ],
+    )
+}
+
+fn subscribe(access: &Access, config: &Pubkey, treasury: &Pubkey) -> Instruction {
+    let user = access.user.pubkey();
+    nft_instruction(
+        AccessInstruction::Subscribe {
+            tier: AccessTier::Pro,
+            periods: 1,
+        },
+        vec![
+            AccountMeta::new(access_address(&user), false),
+            AccountMeta::new_readonly(user, true),
+            AccountMeta::new(access.user_token_account, false),
+            AccountMeta::new_readonly(*config, false),
+            AccountMeta::new(*treasury, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    )
+}
+
+/// The real config and the user's access account, with a look-alike config alongside
+async fn start() -> (ProgramTestContext, Access, Pubkey) {
+    let mut program_test = program_test();
+    let access = add_access_accounts(&mut program_test);
+    let look_alike = add_look_alike_config(&mut program_test, &access);
+    let mut context = program_test.start_with_context().await;
+    set_unix_timestamp(&mut context, START).await;
+
+    let (config, config_bump) = pda(&[CONFIG_SEED]);
+    let user = access.user.pubkey();
+    let (_, access_bump) = pda(&[ACCESS_SEED, user.as_ref()]);
+    process(&mut context, &[initialize_config(&access, &config, config_bump)], &[&access.authority])
+        .await
+        .unwrap();
+    process(
+        &mut context,
+        &[nft_instruction(
+            AccessInstruction::InitializeAccess { bump: access_bump },
+            vec![
+                AccountMeta::new(access_address(&user), false),
+                AccountMeta::new(user, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        )],
+        &[&access.user],
+    )
+    .await
+    .unwrap();
+    (context, access, look_alike)
+}
+
+#[tokio::test]
+async fn config_is_created_once_and_only_at_its_pda() {
+    let (mut context, access, _) = start().await;
+    let (config, config_bump) = pda(&[CONFIG_SEED]);
+
+    let result = process(&mut context, &[initialize_config(&access, &config, config_bump)], &[&access.authority]).await;
+    assert_eq!(instruction_error(result), InstructionError::AccountAlreadyInitialized);
+
+    let elsewhere = Pubkey::new_unique();
+    let result = process(&mut context, &[initialize_config(&access, &elsewhere, config_bump)], &[&access.authority]).await;
+    assert_eq!(instruction_error(result), InstructionError::InvalidSeeds);
+}
+
+#[tokio::test]
+async fn verify_rejects_a_look_alike_config() {
+    let (mut context, access, look_alike) = start().await;
+
+    let result = process(&mut context, &[verify_access(&access, &look_alike)], &[&access.user]).await;
+    assert_eq!(instruction_error(result), InstructionError::InvalidSeeds);
+
+    // The real config holds the upgrade until the default slot gap has passed
+    process(&mut context, &[verify_access(&access, &config_address())], &[&access.user])
+        .await
+        .unwrap();
+    let account: AccessAccount = get_state(&mut context, &access_address(&access.user.pubkey())).await;
+    assert_eq!(account.current_tier, AccessTier::None);
+    assert_eq!(account.pending_tier, AccessTier::Elite);
+
+    let slot = context.banks_client.get_root_slot().await.unwrap();
+    context.warp_to_slot(slot + 200).unwrap();
+    set_unix_timestamp(&mut context, START + 60).await;
+    process(&mut context, &[verify_access(&access, &config_address())], &[&access.user])
+        .await
+        .unwrap();
+    let account: AccessAccount = get_state(&mut context, &access_address(&access.user.pubkey())).await;
+    assert_eq!(account.current_tier, AccessTier::Elite);
+}
+
+#[tokio::test]
+async fn grants_need_the_real_config_and_its_authority() {
+    let (mut context, access, look_alike) = start().await;
+    let attacker = access.attacker.pubkey();
+
+    let result = process(&mut context, &[grant_tier(&access, &look_alike, &attacker)], &[&access.attacker]).await;
+    assert_eq!(instruction_error(result), InstructionError::InvalidSeeds);
+
+    let result = process(&mut context, &[grant_tier(&access, &config_address(), &attacker)], &[&access.attacker]).await;
+    assert_eq!(instruction_error(result), InstructionError::InvalidAccountOwner);
+
+    let authority = access.authority.pubkey();
+    process(&mut context, &[grant_tier(&access, &config_address(), &authority)], &[&access.authority])
+        .await
+        .unwrap();
+    let account: AccessAccount = get_state(&mut context, &access_address(&access.user.pubkey())).await;
+    assert_eq!(account.granted_tier, AccessTier::Elite);
+}
+
+#[tokio::test]
+async fn subscribe_pays_only_the_real_configs_treasury() {
+    let (mut context, access, look_alike) = start().await;
+    let treasury = Pubkey::new_unique();
+    context.set_account(
+        &treasury,
+        &account_with_data(
+            token_account_data(&access.token_mint, &access.authority.pubkey(), 0),
+            spl_token::id(),
+        )
+        .into(),
+    );
+    process(
+        &mut context,
+        &[nft_instruction(
+            AccessInstruction::SetSubscriptionTerms {
+                mint: access.token_mint,
+                treasury,
+                prices: [100, 200, 300],
+            },
+            vec![
+                AccountMeta::new(config_address(), false),
+                AccountMeta::new_readonly(access.authority.pubkey(), true),
+            ],
+        )],
+        &[&access.authority],
+    )
+    .await
+    .unwrap();
+
+    let result = process(
+        &mut context,
+        &[subscribe(&access, &look_alike, &access.attacker_token_account)],
+        &[&access.user],
+    )
+    .await;
+    assert_eq!(instruction_error(result), InstructionError::InvalidSeeds);
+
+    let result = process(
+        &mut context,
+        &[subscribe(&access, &config_address(), &access.attacker_token_account)],
+        &[&access.user],
+    )
+    .await;
+    assert_eq!(instruction_error(result), InstructionError::InvalidArgument);
+
+    process(&mut context, &[subscribe(&access, &config_address(), &treasury)], &[&access.user])
+        .await
+        .unwrap();
+    assert_eq!(token_balance(&mut context, &treasury).await, 200);
+    assert_eq!(token_balance(&mut context, &access.attacker_token_account).await, 0);
+    let account: AccessAccount = get_state(&mut context, &access_address(&access.user.pubkey())).await;
+    assert_eq!(account.subscription_tier, AccessTier::Pro);
+}
+
+#[tokio::test]
+async fn sync_keeps_the_nft_of_a_stale_holder_while_the_rise_is_pending() {
+    let (mut context, access, _) = start().await;
+    let user = access.user.pubkey();
+
+    // A soulbound Elite NFT whose verification has gone stale under the default slot gap
+    let nft_mint = Pubkey::new_unique();
+    context.set_account(&nft_mint, &account_with_data(vec![0; 82], spl_token_2022::id()).into());
+    let mut account: AccessAccount = get_state(&mut context, &access_address(&user)).await;
+    account.current_tier = AccessTier::Elite;
+    account.verification_timestamp = START;
+    account.nft_mint = nft_mint;
+    account.nft_tier = AccessTier::Elite;
+    context.set_account(
+        &access_address(&user),
+        &account_with_data(account.try_to_vec().unwrap(), dregan_nft::id()).into(),
+    );
+    set_unix_timestamp(&mut context, START + DEFAULT_VERIFICATION_VALIDITY + 1).await;
+
+    let result = process(
+        &mut context,
+        &[nft_instruction(
+            AccessInstruction::SyncNft,
+            vec![
+                AccountMeta::new(access_address(&user), false),
+                AccountMeta::new(user, true),
+                AccountMeta::new_readonly(access.user_token_account, false),
+                AccountMeta::new_readonly(config_address(), false),
+                AccountMeta::new(nft_mint, false),
+                AccountMeta::new(Pubkey::new_unique(), false),
+                AccountMeta::new_readonly(pda(&[MINT_AUTHORITY_SEED]).0, false),
+                AccountMeta::new(Pubkey::new_unique(), false),
+                AccountMeta::new(Pubkey::new_unique(), false),
+                AccountMeta::new(Pubkey::new_unique(), false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(spl_token_2022::id(), false),
+                AccountMeta::new_readonly(mpl_token_metadata::ID, false),
+                AccountMeta::new_readonly(sysvar::instructions::id(), false),
+            ],
+        )],
+        &[&access.user],
+    )
+    .await;
+    assert_eq!(instruction_error(result), InstructionError::Custom(4));
+    let account: AccessAccount = get_state(&mut context, &access_address(&user)).await;
+    assert_eq!(account.nft_mint, nft_mint);
+    assert_eq!(account.nft_tier, AccessTier::Elite);
+}
+
+fn legacy_config_data(access: &Access) -> Vec<u8> {
+    let mut data = vec![1];
+    data.extend_from_slice(access.authority.pubkey().as_ref());
+    data.extend_from_slice(access.token_mint.as_ref());
+    data.push(255);
+    data
+}
+
+fn legacy_access_data(owner: &Pubkey, bump: u8) -> Vec<u8> {
+    let mut data = vec![1];
+    data.extend_from_slice(owner.as_ref());
+    data.push(2); // Pro
+    data.extend_from_slice(&5_000u64.to_le_bytes());
+    data.extend_from_slice(&START.to_le_bytes());
+    data.push(bump);
+    data
+}
+
+fn migrate_config(legacy_config: &Pubkey, access: &Access, upgrade_authority: &Pubkey, program_data: &Pubkey) -> Instruction {
+    let (config, bump) = pda(&[CONFIG_SEED]);
+    nft_instruction(
+        AccessInstruction::MigrateConfig { bump },
+        vec![
+            AccountMeta::new(*legacy_config, false),
+            AccountMeta::new(config, false),
+            AccountMeta::new(*upgrade_authority, true),
+            AccountMeta::new_readonly(*program_data, false),
+            AccountMeta::new_readonly(access.token_mint, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn migration_moves_a_legacy_config_and_grows_access_accounts() {
+    let mut program_test = program_test();
+    let access = add_access_accounts(&mut program_test);
+    let upgrade_authority = add_wallet(&mut program_test);
+    let program_data = add_program_data(&mut program_test, &dregan_nft::id(), &upgrade_authority.pubkey());
+    let legacy_config = Pubkey::new_unique();
+    program_test.add_account(
+        legacy_config,
+        account_with_data(legacy_config_data(&access), dregan_nft::id()),
+    );
+    let user = access.user.pubkey();
+    let (access_account, access_bump) = pda(&[ACCESS_SEED, user.as_ref()]);
+    program_test.add_account(
+        access_account,
+        account_with_data(legacy_access_data(&user, access_bump), dregan_nft::id()),
+    );
+    let mut context = program_test.start_with_context().await;
+    set_unix_timestamp(&mut context, START).await;
+
+    // Only the upgrade authority may migrate
+    let result = process(
+        &mut context,
+        &[migrate_config(&legacy_config, &access, &access.authority.pubkey(), &program_data)],
+        &[&access.authority],
+    )
+    .await;
+    assert_eq!(instruction_error(result), InstructionError::MissingRequiredSignature);
+
+    process(
+        &mut context,
+        &[migrate_config(&legacy_config, &access, &upgrade_authority.pubkey(), &program_data)],
+        &[&upgrade_authority],
+    )
+    .await
+    .unwrap();
+    assert!(context.banks_client.get_account(legacy_config).await.unwrap().is_none());
+    let config: AccessConfig = get_state(&mut context, &config_address()).await;
+    assert_eq!(config.authority, access.authority.pubkey());
+    assert_eq!(config.token_mint, access.token_mint);
+
+    // Unmigrated access accounts cannot verify; anyone can migrate them
+    let result = process(&mut context, &[verify_access(&access, &config_address())], &[&access.user]).await;
+    assert!(result.is_err());
+    let payer = context.payer.pubkey();
+    process(
+        &mut context,
+        &[nft_instruction(
+            AccessInstruction::MigrateAccess,
+            vec![
+                AccountMeta::new(access_account, false),
+                AccountMeta::new(payer, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        )],
+        &[],
+    )
+    .await
+    .unwrap();
+    let account: AccessAccount = get_state(&mut context, &access_account).await;
+    assert_eq!(account.owner, user);
+    assert_eq!(account.current_tier, AccessTier::Pro);
+    assert_eq!(account.last_verified_balance, 5_000);
+    assert_eq!(account.bump, access_bump);
+    let lamports = context.banks_client.get_balance(access_account).await.unwrap();
+    assert!(lamports >= Rent::default().minimum_balance(AccessAccount::LEN));
+
+    process(&mut context, &[verify_access(&access, &config_address())], &[&access.user])
+        .await
+        .unwrap();
+    let account: AccessAccount = get_state(&mut context, &access_account).await;
+    assert_eq!(account.pending_tier, AccessTier::Elite);
+}
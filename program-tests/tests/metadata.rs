@@ -0,0 +1,317 @@
+//! Access NFTs through the real Token Metadata program, dumped into
+//! tests/fixtures/mpl_token_metadata.so on the first run
+
+use borsh::BorshSerialize;
+use dregan_nft::{
+    AccessAccount, AccessConfig, AccessInstruction, AccessTier, ACCESS_SEED, COLLECTION_SEED,
+    CONFIG_SEED, DEFAULT_VERIFICATION_VALIDITY, MINT_AUTHORITY_SEED, NFT_MINT_SEED,
+};
+use dregan_program_tests::*;
+use mpl_token_metadata::accounts::{MasterEdition, Metadata};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    clock::Clock,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_program, sysvar,
+};
+use spl_associated_token_account::get_associated_token_address;
+
+fn nft_instruction(instruction: AccessInstruction, accounts: Vec<AccountMeta>) -> Instruction {
+    Instruction {
+        program_id: dregan_nft::id(),
+        accounts,
+        data: instruction.try_to_vec().unwrap(),
+    }
+}
+
+fn pda(seeds: &[&[u8]]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(seeds, &dregan_nft::id())
+}
+
+struct Setup {
+    context: ProgramTestContext,
+    user: Keypair,
+    token_mint: Pubkey,
+    user_token_account: Pubkey,
+}
+
+/// Config, collection and the user's access account, with the user holding
+/// `balance` DREGAN (0 decimals). `slot_gap` overrides the default verification slot gap.
+async fn setup(balance: u64, slot_gap: Option<u64>) -> Setup {
+    let mut program_test = program_test();
+    add_token_metadata_program(&mut program_test).await;
+    let authority = add_wallet(&mut program_test);
+    let user = add_wallet(&mut program_test);
+    let token_mint = add_mint(&mut program_test, &authority.pubkey(), 0);
+    let user_token_account = add_token_account(&mut program_test, &token_mint, &user.pubkey(), balance);
+    let mut context = program_test.start_with_context().await;
+
+    let (config, config_bump) = pda(&[CONFIG_SEED]);
+    let (collection_mint, _) = pda(&[COLLECTION_SEED]);
+    let (mint_authority, _) = pda(&[MINT_AUTHORITY_SEED]);
+    let (access_account, access_bump) = pda(&[ACCESS_SEED, user.pubkey().as_ref()]);
+    let mut instructions = vec![nft_instruction(
+        AccessInstruction::InitializeConfig { bump: config_bump },
+        vec![
+            AccountMeta::new(config, false),
+            AccountMeta::new(authority.pubkey(), true),
+            AccountMeta::new_readonly(token_mint, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )];
+    if let Some(slots) = slot_gap {
+        instructions.push(nft_instruction(
+            AccessInstruction::SetVerificationSlotGap { slots },
+            vec![
+                AccountMeta::new(config, false),
+                AccountMeta::new_readonly(authority.pubkey(), true),
+            ],
+        ));
+    }
+    process(&mut context, &instructions, &[&authority]).await.unwrap();
+
+    process(
+        &mut context,
+        &[nft_instruction(
+            AccessInstruction::CreateCollection,
+            vec![
+                AccountMeta::new(config, false),
+                AccountMeta::new(authority.pubkey(), true),
+                AccountMeta::new(collection_mint, false),
+                AccountMeta::new(get_associated_token_address(&authority.pubkey(), &collection_mint), false),
+                AccountMeta::new_readonly(mint_authority, false),
+                AccountMeta::new(Metadata::find_pda(&collection_mint).0, false),
+                AccountMeta::new(MasterEdition::find_pda(&collection_mint).0, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+                AccountMeta::new_readonly(mpl_token_metadata::ID, false),
+            ],
+        )],
+        &[&authority],
+    )
+    .await
+    .unwrap();
+
+    process(
+        &mut context,
+        &[nft_instruction(
+            AccessInstruction::InitializeAccess { bump: access_bump },
+            vec![
+                AccountMeta::new(access_account, false),
+                AccountMeta::new(user.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        )],
+        &[&user],
+    )
+    .await
+    .unwrap();
+
+    Setup {
+        context,
+        user,
+        token_mint,
+        user_token_account,
+    }
+}
+
+fn verify_access(setup: &Setup) -> Instruction {
+    let user = setup.user.pubkey();
+    nft_instruction(
+        AccessInstruction::VerifyAccess,
+        vec![
+            AccountMeta::new(pda(&[ACCESS_SEED, user.as_ref()]).0, false),
+            AccountMeta::new_readonly(user, true),
+            AccountMeta::new_readonly(setup.user_token_account, false),
+            AccountMeta::new_readonly(pda(&[CONFIG_SEED]).0, false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+        ],
+    )
+}
+
+/// Verify, then move past the default slot gap so the next reading confirms the rise
+async fn verify_and_wait(setup: &mut Setup) {
+    let instruction = verify_access(setup);
+    process(&mut setup.context, &[instruction], &[&setup.user]).await.unwrap();
+    let slot = setup.context.banks_client.get_root_slot().await.unwrap();
+    setup.context.warp_to_slot(slot + 200).unwrap();
+}
+
+fn mint_access_nft(setup: &Setup, nft_mint: &Pubkey) -> Instruction {
+    let user = setup.user.pubkey();
+    let (collection_mint, _) = pda(&[COLLECTION_SEED]);
+    nft_instruction(
+        AccessInstruction::MintAccessNft,
+        vec![
+            AccountMeta::new(pda(&[ACCESS_SEED, user.as_ref()]).0, false),
+            AccountMeta::new(user, true),
+            AccountMeta::new_readonly(setup.user_token_account, false),
+            AccountMeta::new_readonly(pda(&[CONFIG_SEED]).0, false),
+            AccountMeta::new(*nft_mint, false),
+            AccountMeta::new(get_associated_token_address(&user, nft_mint), false),
+            AccountMeta::new_readonly(pda(&[MINT_AUTHORITY_SEED]).0, false),
+            AccountMeta::new(Metadata::find_pda(nft_mint).0, false),
+            AccountMeta::new(MasterEdition::find_pda(nft_mint).0, false),
+            AccountMeta::new_readonly(collection_mint, false),
+            AccountMeta::new(Metadata::find_pda(&collection_mint).0, false),
+            AccountMeta::new_readonly(MasterEdition::find_pda(&collection_mint).0, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+            AccountMeta::new_readonly(mpl_token_metadata::ID, false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+        ],
+    )
+}
+
+fn sync_nft(setup: &Setup, nft_mint: &Pubkey) -> Instruction {
+    let user = setup.user.pubkey();
+    let (collection_mint, _) = pda(&[COLLECTION_SEED]);
+    nft_instruction(
+        AccessInstruction::SyncNft,
+        vec![
+            AccountMeta::new(pda(&[ACCESS_SEED, user.as_ref()]).0, false),
+            AccountMeta::new(user, true),
+            AccountMeta::new_readonly(setup.user_token_account, false),
+            AccountMeta::new_readonly(pda(&[CONFIG_SEED]).0, false),
+            AccountMeta::new(*nft_mint, false),
+            AccountMeta::new(get_associated_token_address(&user, nft_mint), false),
+            AccountMeta::new_readonly(pda(&[MINT_AUTHORITY_SEED]).0, false),
+            AccountMeta::new(Metadata::find_pda(nft_mint).0, false),
+            AccountMeta::new(MasterEdition::find_pda(nft_mint).0, false),
+            AccountMeta::new(Metadata::find_pda(&collection_mint).0, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(mpl_token_metadata::ID, false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+        ],
+    )
+}
+
+async fn metadata(context: &mut ProgramTestContext, nft_mint: &Pubkey) -> Metadata {
+    let account = context
+        .banks_client
+        .get_account(Metadata::find_pda(nft_mint).0)
+        .await
+        .unwrap()
+        .expect("metadata account exists");
+    Metadata::safe_deserialize(&account.data).unwrap()
+}
+
+#[tokio::test]
+async fn mint_uses_the_tier_metadata_and_verifies_the_collection() {
+    let mut setup = setup(30_000, Some(0)).await;
+    let user = setup.user.pubkey();
+    let (nft_mint, _) = pda(&[NFT_MINT_SEED, user.as_ref(), &0u32.to_le_bytes()]);
+
+    let instruction = mint_access_nft(&setup, &nft_mint);
+    process(&mut setup.context, &[instruction], &[&setup.user]).await.unwrap();
+
+    let config: AccessConfig = get_state(&mut setup.context, &pda(&[CONFIG_SEED]).0).await;
+    let elite = config.metadata_for(&AccessTier::Elite).unwrap();
+    let metadata = metadata(&mut setup.context, &nft_mint).await;
+    assert_eq!(metadata.name.trim_end_matches('\0'), elite.name());
+    assert_eq!(metadata.symbol.trim_end_matches('\0'), elite.symbol());
+    assert_eq!(metadata.uri.trim_end_matches('\0'), elite.uri());
+    assert_eq!(metadata.update_authority, pda(&[MINT_AUTHORITY_SEED]).0);
+    let collection = metadata.collection.expect("collection set");
+    assert_eq!(collection.key, config.collection_mint);
+    assert!(collection.verified);
+
+    assert_eq!(token_balance(&mut setup.context, &get_associated_token_address(&user, &nft_mint)).await, 1);
+    let access: AccessAccount = get_state(&mut setup.context, &pda(&[ACCESS_SEED, user.as_ref()]).0).await;
+    assert_eq!(access.nft_mint, nft_mint);
+    assert_eq!(access.nft_tier, AccessTier::Elite);
+}
+
+#[tokio::test]
+async fn sync_moves_the_metadata_to_the_new_tier_uri() {
+    let mut setup = setup(30_000, Some(0)).await;
+    let user = setup.user.pubkey();
+    let (nft_mint, _) = pda(&[NFT_MINT_SEED, user.as_ref(), &0u32.to_le_bytes()]);
+    let instruction = mint_access_nft(&setup, &nft_mint);
+    process(&mut setup.context, &[instruction], &[&setup.user]).await.unwrap();
+
+    let (user_token_account, token_mint) = (setup.user_token_account, setup.token_mint);
+    set_token_balance(&mut setup.context, &user_token_account, &token_mint, &user, 6_000);
+    let instruction = sync_nft(&setup, &nft_mint);
+    process(&mut setup.context, &[instruction], &[&setup.user]).await.unwrap();
+
+    let config: AccessConfig = get_state(&mut setup.context, &pda(&[CONFIG_SEED]).0).await;
+    let pro = config.metadata_for(&AccessTier::Pro).unwrap();
+    let metadata = metadata(&mut setup.context, &nft_mint).await;
+    assert_eq!(metadata.name.trim_end_matches('\0'), pro.name());
+    assert_eq!(metadata.uri.trim_end_matches('\0'), pro.uri());
+    let access: AccessAccount = get_state(&mut setup.context, &pda(&[ACCESS_SEED, user.as_ref()]).0).await;
+    assert_eq!(access.nft_tier, AccessTier::Pro);
+}
+
+#[tokio::test]
+async fn sync_burns_the_nft_when_access_is_lost() {
+    let mut setup = setup(30_000, Some(0)).await;
+    let user = setup.user.pubkey();
+    let (nft_mint, _) = pda(&[NFT_MINT_SEED, user.as_ref(), &0u32.to_le_bytes()]);
+    let instruction = mint_access_nft(&setup, &nft_mint);
+    process(&mut setup.context, &[instruction], &[&setup.user]).await.unwrap();
+
+    let (user_token_account, token_mint) = (setup.user_token_account, setup.token_mint);
+    set_token_balance(&mut setup.context, &user_token_account, &token_mint, &user, 0);
+    let instruction = sync_nft(&setup, &nft_mint);
+    process(&mut setup.context, &[instruction], &[&setup.user]).await.unwrap();
+
+    let metadata_account = setup
+        .context
+        .banks_client
+        .get_account(Metadata::find_pda(&nft_mint).0)
+        .await
+        .unwrap();
+    assert!(metadata_account.is_none());
+    let access: AccessAccount = get_state(&mut setup.context, &pda(&[ACCESS_SEED, user.as_ref()]).0).await;
+    assert_eq!(access.nft_mint, Pubkey::default());
+    assert_eq!(access.nft_generation, 1);
+}
+
+#[tokio::test]
+async fn default_slot_gap_confirms_the_mint_and_keeps_a_stale_holders_nft() {
+    let mut setup = setup(30_000, None).await;
+    let user = setup.user.pubkey();
+    let (nft_mint, _) = pda(&[NFT_MINT_SEED, user.as_ref(), &0u32.to_le_bytes()]);
+
+    // The first reading only records the rise
+    let instruction = mint_access_nft(&setup, &nft_mint);
+    let result = process(&mut setup.context, &[instruction], &[&setup.user]).await;
+    assert_eq!(instruction_error(result), InstructionError::Custom(4));
+
+    verify_and_wait(&mut setup).await;
+    let instruction = mint_access_nft(&setup, &nft_mint);
+    process(&mut setup.context, &[instruction], &[&setup.user]).await.unwrap();
+    let access: AccessAccount = get_state(&mut setup.context, &pda(&[ACCESS_SEED, user.as_ref()]).0).await;
+    assert_eq!(access.nft_tier, AccessTier::Elite);
+
+    // Once the verification is stale, syncing holds the rise instead of burning the NFT
+    let clock: Clock = setup.context.banks_client.get_sysvar().await.unwrap();
+    set_unix_timestamp(&mut setup.context, clock.unix_timestamp + DEFAULT_VERIFICATION_VALIDITY + 1).await;
+    let instruction = sync_nft(&setup, &nft_mint);
+    let result = process(&mut setup.context, &[instruction], &[&setup.user]).await;
+    assert_eq!(instruction_error(result), InstructionError::Custom(4));
+    assert!(setup
+        .context
+        .banks_client
+        .get_account(Metadata::find_pda(&nft_mint).0)
+        .await
+        .unwrap()
+        .is_some());
+
+    verify_and_wait(&mut setup).await;
+    let instruction = sync_nft(&setup, &nft_mint);
+    process(&mut setup.context, &[instruction], &[&setup.user]).await.unwrap();
+    let config: AccessConfig = get_state(&mut setup.context, &pda(&[CONFIG_SEED]).0).await;
+    let elite = config.metadata_for(&AccessTier::Elite).unwrap();
+    assert_eq!(metadata(&mut setup.context, &nft_mint).await.uri.trim_end_matches('\0'), elite.uri());
+    let access: AccessAccount = get_state(&mut setup.context, &pda(&[ACCESS_SEED, user.as_ref()]).0).await;
+    assert_eq!(access.current_tier, AccessTier::Elite);
+    assert_eq!(access.nft_tier, AccessTier::Elite);
+}
@@ -0,0 +1,535 @@
+//! dregan-staking: the pool PDA, its vault checks and the legacy pool migration
+
+use borsh::BorshSerialize;
+use dregan_program_tests::*;
+use dregan_staking::{
+    PoolConfig, StakeAccount, StakeInstruction, StakeTier, POOL_SEED, STAKE_SEED, VAULT_SEED,
+};
+use solana_program_test::{ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_program,
+};
+use spl_token::state::Account as TokenAccount;
+
+const REWARD_FUNDING: u64 = 10_000;
+const USER_BALANCE: u64 = 100_000;
+
+fn stake_instruction(instruction: StakeInstruction, accounts: Vec<AccountMeta>) -> Instruction {
+    Instruction {
+        program_id: dregan_staking::id(),
+        accounts,
+        data: instruction.try_to_vec().unwrap(),
+    }
+}
+
+fn pda(seeds: &[&[u8]]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(seeds, &dregan_staking::id())
+}
+
+fn pool_address() -> Pubkey {
+    pda(&[POOL_SEED]).0
+}
+
+fn vault_authority() -> Pubkey {
+    pda(&[VAULT_SEED, pool_address().as_ref()]).0
+}
+
+struct Pool {
+    authority: Keypair,
+    user: Keypair,
+    token_mint: Pubkey,
+    stake_vault: Pubkey,
+    reward_vault: Pubkey,
+    user_token_account: Pubkey,
+}
+
+/// Wallets, the mint, both vaults (owned by `vault_owner`) and a user balance
+fn add_pool_accounts(program_test: &mut ProgramTest, vault_owner: &Pubkey) -> Pool {
+    let authority = add_wallet(program_test);
+    let user = add_wallet(program_test);
+    let token_mint = add_mint(program_test, &authority.pubkey(), 0);
+    let stake_vault = add_token_account(program_test, &token_mint, vault_owner, 0);
+    let reward_vault = add_token_account(program_test, &token_mint, vault_owner, REWARD_FUNDING);
+    let user_token_account = add_token_account(program_test, &token_mint, &user.pubkey(), USER_BALANCE);
+    Pool {
+        authority,
+        user,
+        token_mint,
+        stake_vault,
+        reward_vault,
+        user_token_account,
+    }
+}
+
+fn initialize_pool(pool: &Pool, stake_vault: &Pubkey) -> Instruction {
+    let (pool_config, bump) = pda(&[POOL_SEED]);
+    stake_instruction(
+        StakeInstruction::InitializePool { bump },
+        vec![
+            AccountMeta::new(pool_config, false),
+            AccountMeta::new(pool.authority.pubkey(), true),
+            AccountMeta::new_readonly(pool.token_mint, false),
+            AccountMeta::new_readonly(*stake_vault, false),
+            AccountMeta::new_readonly(pool.reward_vault, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+fn initialize_stake(owner: &Pubkey) -> Instruction {
+    let (stake_account, bump) = pda(&[STAKE_SEED, owner.as_ref()]);
+    stake_instruction(
+        StakeInstruction::InitializeStake { bump },
+        vec![
+            AccountMeta::new(stake_account, false),
+            AccountMeta::new(*owner, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+fn stake(pool: &Pool, amount: u64, tier: StakeTier) -> Instruction {
+    let owner = pool.user.pubkey();
+    stake_instruction(
+        StakeInstruction::Stake { amount, tier },
+        vec![
+            AccountMeta::new(pda(&[STAKE_SEED, owner.as_ref()]).0, false),
+            AccountMeta::new_readonly(owner, true),
+            AccountMeta::new(pool.user_token_account, false),
+            AccountMeta::new(pool.stake_vault, false),
+            AccountMeta::new(pool_address(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    )
+}
+
+fn claim_rewards(pool: &Pool, pool_config: &Pubkey, reward_vault: &Pubkey) -> Instruction {
+    let owner = pool.user.pubkey();
+    stake_instruction(
+        StakeInstruction::ClaimRewards,
+        vec![
+            AccountMeta::new(pda(&[STAKE_SEED, owner.as_ref()]).0, false),
+            AccountMeta::new_readonly(owner, true),
+            AccountMeta::new(pool.user_token_account, false),
+            AccountMeta::new(*reward_vault, false),
+            AccountMeta::new(*pool_config, false),
+            AccountMeta::new_readonly(pda(&[VAULT_SEED, pool_config.as_ref()]).0, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    )
+}
+
+fn unstake(pool: &Pool) -> Instruction {
+    let owner = pool.user.pubkey();
+    stake_instruction(
+        StakeInstruction::Unstake,
+        vec![
+            AccountMeta::new(pda(&[STAKE_SEED, owner.as_ref()]).0, false),
+            AccountMeta::new_readonly(owner, true),
+            AccountMeta::new(pool.user_token_account, false),
+            AccountMeta::new(pool.stake_vault, false),
+            AccountMeta::new(pool_address(), false),
+            AccountMeta::new_readonly(vault_authority(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    )
+}
+
+fn authority_instruction(pool: &Pool, instruction: StakeInstruction) -> Instruction {
+    stake_instruction(
+        instruction,
+        vec![
+            AccountMeta::new(pool_address(), false),
+            AccountMeta::new_readonly(pool.authority.pubkey(), true),
+        ],
+    )
+}
+
+fn withdraw_surplus_rewards(pool: &Pool, signer: &Pubkey, destination: &Pubkey, amount: u64) -> Instruction {
+    stake_instruction(
+        StakeInstruction::WithdrawSurplusRewards { amount },
+        vec![
+            AccountMeta::new(pool_address(), false),
+            AccountMeta::new_readonly(*signer, true),
+            AccountMeta::new(pool.reward_vault, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(vault_authority(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    )
+}
+
+/// A running pool with the user staking `amount` at `tier` from START
+async fn staked_pool(amount: u64, tier: StakeTier) -> (ProgramTestContext, Pool) {
+    let mut program_test = program_test();
+    let pool = add_pool_accounts(&mut program_test, &vault_authority());
+    let mut context = program_test.start_with_context().await;
+    set_unix_timestamp(&mut context, START).await;
+
+    process(&mut context, &[initialize_pool(&pool, &pool.stake_vault)], &[&pool.authority])
+        .await
+        .unwrap();
+    process(
+        &mut context,
+        &[initialize_stake(&pool.user.pubkey()), stake(&pool, amount, tier)],
+        &[&pool.user],
+    )
+    .await
+    .unwrap();
+    (context, pool)
+}
+
+#[tokio::test]
+async fn unstake_skips_the_cooldown_once_the_campaign_has_ended() {
+    let (mut context, pool) = staked_pool(USER_BALANCE, StakeTier::Elite).await;
+    let campaign = |campaign_end| {
+        authority_instruction(
+            &pool,
+            StakeInstruction::SetCampaign {
+                campaign_start: 0,
+                deposit_end: START,
+                campaign_end,
+            },
+        )
+    };
+    process(
+        &mut context,
+        &[
+            authority_instruction(
+                &pool,
+                StakeInstruction::SetUnbondingCooldowns {
+                    unbonding_cooldowns: [0, 0, 7 * DAY, 0],
+                },
+            ),
+            campaign(START + 100 * DAY),
+        ],
+        &[&pool.authority],
+    )
+    .await
+    .unwrap();
+
+    // Past the lock but inside the campaign, the Elite cooldown applies
+    set_unix_timestamp(&mut context, START + 91 * DAY).await;
+    let result = process(&mut context, &[unstake(&pool)], &[&pool.user]).await;
+    assert_eq!(instruction_error(result), InstructionError::Custom(18));
+
+    process(&mut context, &[campaign(START + 91 * DAY)], &[&pool.authority])
+        .await
+        .unwrap();
+    process(&mut context, &[unstake(&pool)], &[&pool.user]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &pool.user_token_account).await, USER_BALANCE);
+    let config: PoolConfig = get_state(&mut context, &pool_address()).await;
+    assert_eq!(config.total_staked, 0);
+}
+
+#[tokio::test]
+async fn initialize_rejects_vaults_outside_the_vault_authority() {
+    let mut program_test = program_test();
+    let attacker = Pubkey::new_unique();
+    let pool = add_pool_accounts(&mut program_test, &vault_authority());
+    let attacker_vault = add_token_account(&mut program_test, &pool.token_mint, &attacker, 0);
+    let mut context = program_test.start_with_context().await;
+
+    let result = process(&mut context, &[initialize_pool(&pool, &attacker_vault)], &[&pool.authority]).await;
+    assert_eq!(instruction_error(result), InstructionError::InvalidAccountData);
+
+    let result = process(&mut context, &[initialize_pool(&pool, &pool.reward_vault)], &[&pool.authority]).await;
+    assert_eq!(instruction_error(result), InstructionError::InvalidArgument);
+}
+
+#[tokio::test]
+async fn initialize_creates_the_pool_once() {
+    let mut program_test = program_test();
+    let pool = add_pool_accounts(&mut program_test, &vault_authority());
+    let mut context = program_test.start_with_context().await;
+
+    process(&mut context, &[initialize_pool(&pool, &pool.stake_vault)], &[&pool.authority])
+        .await
+        .unwrap();
+    let config: PoolConfig = get_state(&mut context, &pool_address()).await;
+    assert_eq!(config.authority, pool.authority.pubkey());
+    assert_eq!(config.reward_vault, pool.reward_vault);
+
+    let result = process(&mut context, &[initialize_pool(&pool, &pool.stake_vault)], &[&pool.authority]).await;
+    assert_eq!(instruction_error(result), InstructionError::AccountAlreadyInitialized);
+}
+
+#[tokio::test]
+async fn claim_pays_accrued_rewards_from_the_pool_vault() {
+    let (mut context, pool) = staked_pool(USER_BALANCE, StakeTier::Elite).await;
+    let config: PoolConfig = get_state(&mut context, &pool_address()).await;
+    // 100,000 * 20% * 90 / 365 reserved up front
+    assert_eq!(config.reward_liabilities, 4_931);
+
+    set_unix_timestamp(&mut context, START + 45 * DAY).await;
+    process(&mut context, &[claim_rewards(&pool, &pool_address(), &pool.reward_vault)], &[&pool.user])
+        .await
+        .unwrap();
+    assert_eq!(token_balance(&mut context, &pool.user_token_account).await, 2_465);
+    assert_eq!(token_balance(&mut context, &pool.reward_vault).await, REWARD_FUNDING - 2_465);
+    let config: PoolConfig = get_state(&mut context, &pool_address()).await;
+    assert_eq!(config.reward_liabilities, 4_931 - 2_465);
+}
+
+#[tokio::test]
+async fn claim_rejects_a_look_alike_pool_config() {
+    let mut program_test = program_test();
+    let pool = add_pool_accounts(&mut program_test, &vault_authority());
+    // A program-owned account with a pool layout pointing at an attacker vault,
+    // e.g. one left over from before the pool became a PDA
+    let attacker = Keypair::new();
+    let fake_pool = Pubkey::new_unique();
+    let attacker_vault = add_token_account(
+        &mut program_test,
+        &pool.token_mint,
+        &Pubkey::find_program_address(&[VAULT_SEED, fake_pool.as_ref()], &dregan_staking::id()).0,
+        REWARD_FUNDING,
+    );
+    let fake_config = PoolConfig {
+        is_initialized: true,
+        authority: attacker.pubkey(),
+        token_mint: pool.token_mint,
+        stake_vault: pool.stake_vault,
+        reward_vault: attacker_vault,
+        total_staked: 0,
+        total_rewards_distributed: 0,
+        reward_liabilities: 0,
+        min_stake_amount: 1,
+        max_stake_amount: u64::MAX,
+        tier_caps: [u64::MAX; 4],
+        tier_staked: [0; 4],
+        campaign_start: 0,
+        deposit_end: i64::MAX,
+        campaign_end: i64::MAX,
+        unbonding_cooldowns: [0; 4],
+        unmigrated_staked: 0,
+        bump: 255,
+    };
+    program_test.add_account(
+        fake_pool,
+        account_with_data(fake_config.try_to_vec().unwrap(), dregan_staking::id()),
+    );
+    let mut context = program_test.start_with_context().await;
+    set_unix_timestamp(&mut context, START).await;
+    process(&mut context, &[initialize_pool(&pool, &pool.stake_vault)], &[&pool.authority])
+        .await
+        .unwrap();
+    process(
+        &mut context,
+        &[initialize_stake(&pool.user.pubkey()), stake(&pool, USER_BALANCE, StakeTier::Elite)],
+        &[&pool.user],
+    )
+    .await
+    .unwrap();
+
+    set_unix_timestamp(&mut context, START + 45 * DAY).await;
+    let result = process(&mut context, &[claim_rewards(&pool, &fake_pool, &attacker_vault)], &[&pool.user]).await;
+    assert_eq!(instruction_error(result), InstructionError::InvalidSeeds);
+}
+
+#[tokio::test]
+async fn claim_rejects_a_vault_other_than_the_pools() {
+    let (mut context, pool) = staked_pool(USER_BALANCE, StakeTier::Elite).await;
+    set_unix_timestamp(&mut context, START + 45 * DAY).await;
+
+    // Owned by the real vault authority, but not the pool's reward vault
+    let other_vault = Pubkey::new_unique();
+    context.set_account(
+        &other_vault,
+        &account_with_data(token_account_data(&pool.token_mint, &vault_authority(), 0), spl_token::id()).into(),
+    );
+    let result = process(&mut context, &[claim_rewards(&pool, &pool_address(), &other_vault)], &[&pool.user]).await;
+    assert_eq!(instruction_error(result), InstructionError::InvalidArgument);
+
+    let result = process(&mut context, &[claim_rewards(&pool, &pool_address(), &pool.stake_vault)], &[&pool.user]).await;
+    assert_eq!(instruction_error(result), InstructionError::InvalidArgument);
+}
+
+#[tokio::test]
+async fn surplus_withdrawal_leaves_reserved_rewards_in_the_vault() {
+    let (mut context, pool) = staked_pool(USER_BALANCE, StakeTier::Elite).await;
+    let destination = Pubkey::new_unique();
+    context.set_account(
+        &destination,
+        &account_with_data(token_account_data(&pool.token_mint, &pool.authority.pubkey(), 0), spl_token::id()).into(),
+    );
+
+    let attacker = Keypair::new();
+    let result = process(
+        &mut context,
+        &[withdraw_surplus_rewards(&pool, &attacker.pubkey(), &destination, 1)],
+        &[&attacker],
+    )
+    .await;
+    assert_eq!(instruction_error(result), InstructionError::InvalidAccountOwner);
+
+    let surplus = REWARD_FUNDING - 4_931;
+    let result = process(
+        &mut context,
+        &[withdraw_surplus_rewards(&pool, &pool.authority.pubkey(), &destination, surplus + 1)],
+        &[&pool.authority],
+    )
+    .await;
+    assert_eq!(instruction_error(result), InstructionError::Custom(6));
+
+    process(
+        &mut context,
+        &[withdraw_surplus_rewards(&pool, &pool.authority.pubkey(), &destination, surplus)],
+        &[&pool.authority],
+    )
+    .await
+    .unwrap();
+    assert_eq!(token_balance(&mut context, &pool.reward_vault).await, 4_931);
+
+    // The reservation still pays out in full at unlock
+    set_unix_timestamp(&mut context, START + 90 * DAY).await;
+    process(&mut context, &[claim_rewards(&pool, &pool_address(), &pool.reward_vault)], &[&pool.user])
+        .await
+        .unwrap();
+    assert_eq!(token_balance(&mut context, &pool.reward_vault).await, 0);
+}
+
+/// Pool and stake accounts in the layouts written before the pool moved to its PDA
+fn legacy_pool_data(pool: &Pool, total_staked: u64) -> Vec<u8> {
+    let mut data = vec![1];
+    data.extend_from_slice(pool.authority.pubkey().as_ref());
+    data.extend_from_slice(pool.token_mint.as_ref());
+    data.extend_from_slice(pool.stake_vault.as_ref());
+    data.extend_from_slice(pool.reward_vault.as_ref());
+    data.extend_from_slice(&total_staked.to_le_bytes());
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.push(255);
+    data
+}
+
+fn legacy_stake_data(owner: &Pubkey, amount: u64, bump: u8) -> Vec<u8> {
+    let mut data = vec![1];
+    data.extend_from_slice(owner.as_ref());
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.push(2); // Elite
+    data.extend_from_slice(&START.to_le_bytes());
+    data.extend_from_slice(&(START + 90 * DAY).to_le_bytes());
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.push(bump);
+    data
+}
+
+fn migrate_pool(legacy_pool: &Pubkey, pool: &Pool, upgrade_authority: &Pubkey, program_data: &Pubkey) -> Instruction {
+    let (pool_config, bump) = pda(&[POOL_SEED]);
+    stake_instruction(
+        StakeInstruction::MigratePool { bump },
+        vec![
+            AccountMeta::new(*legacy_pool, false),
+            AccountMeta::new(pool_config, false),
+            AccountMeta::new(*upgrade_authority, true),
+            AccountMeta::new_readonly(*program_data, false),
+            AccountMeta::new(pool.stake_vault, false),
+            AccountMeta::new(pool.reward_vault, false),
+            AccountMeta::new_readonly(pda(&[VAULT_SEED]).0, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn migration_moves_a_legacy_pool_and_reserves_its_positions() {
+    let mut program_test = program_test();
+    let pool = add_pool_accounts(&mut program_test, &pda(&[VAULT_SEED]).0);
+    let upgrade_authority = add_wallet(&mut program_test);
+    let program_data = add_program_data(&mut program_test, &dregan_staking::id(), &upgrade_authority.pubkey());
+    let legacy_pool = Pubkey::new_unique();
+    program_test.add_account(
+        legacy_pool,
+        account_with_data(legacy_pool_data(&pool, USER_BALANCE), dregan_staking::id()),
+    );
+    let (stake_account, stake_bump) = pda(&[STAKE_SEED, pool.user.pubkey().as_ref()]);
+    program_test.add_account(
+        stake_account,
+        account_with_data(legacy_stake_data(&pool.user.pubkey(), USER_BALANCE, stake_bump), dregan_staking::id()),
+    );
+    let mut context = program_test.start_with_context().await;
+    set_unix_timestamp(&mut context, START + DAY).await;
+
+    // Only the upgrade authority may migrate
+    let result = process(
+        &mut context,
+        &[migrate_pool(&legacy_pool, &pool, &pool.authority.pubkey(), &program_data)],
+        &[&pool.authority],
+    )
+    .await;
+    assert_eq!(instruction_error(result), InstructionError::MissingRequiredSignature);
+
+    process(
+        &mut context,
+        &[migrate_pool(&legacy_pool, &pool, &upgrade_authority.pubkey(), &program_data)],
+        &[&upgrade_authority],
+    )
+    .await
+    .unwrap();
+    assert!(context.banks_client.get_account(legacy_pool).await.unwrap().is_none());
+    for vault in [pool.stake_vault, pool.reward_vault] {
+        let account: Account = context.banks_client.get_account(vault).await.unwrap().unwrap();
+        assert_eq!(TokenAccount::unpack(&account.data).unwrap().owner, vault_authority());
+    }
+    let config: PoolConfig = get_state(&mut context, &pool_address()).await;
+    assert_eq!(config.authority, pool.authority.pubkey());
+    assert_eq!(config.total_staked, USER_BALANCE);
+    assert_eq!(config.reward_liabilities, 0);
+
+    assert_eq!(config.unmigrated_staked, USER_BALANCE);
+
+    // The unmigrated position's rewards are not surplus yet
+    let destination = Pubkey::new_unique();
+    context.set_account(
+        &destination,
+        &account_with_data(
+            token_account_data(&pool.token_mint, &pool.authority.pubkey(), 0),
+            spl_token::id(),
+        )
+        .into(),
+    );
+    let withdraw = || withdraw_surplus_rewards(&pool, &pool.authority.pubkey(), &destination, 1);
+    let result = process(&mut context, &[withdraw()], &[&pool.authority]).await;
+    assert_eq!(instruction_error(result), InstructionError::Custom(19));
+
+    // Unmigrated positions cannot claim; anyone can migrate them
+    let result = process(&mut context, &[claim_rewards(&pool, &pool_address(), &pool.reward_vault)], &[&pool.user]).await;
+    assert!(result.is_err());
+    let payer = context.payer.pubkey();
+    process(
+        &mut context,
+        &[stake_instruction(
+            StakeInstruction::MigrateStake,
+            vec![
+                AccountMeta::new(stake_account, false),
+                AccountMeta::new(pool_address(), false),
+                AccountMeta::new(payer, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        )],
+        &[],
+    )
+    .await
+    .unwrap();
+    let position: StakeAccount = get_state(&mut context, &stake_account).await;
+    assert_eq!(position.amount, USER_BALANCE);
+    assert_eq!(position.accrual_end_timestamp, START + 90 * DAY);
+    let config: PoolConfig = get_state(&mut context, &pool_address()).await;
+    assert_eq!(config.reward_liabilities, position.reserved_rewards());
+    assert_eq!(config.tier_staked[StakeTier::Elite.index()], USER_BALANCE);
+    assert_eq!(config.unmigrated_staked, 0);
+    process(&mut context, &[withdraw()], &[&pool.authority]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &destination).await, 1);
+
+    set_unix_timestamp(&mut context, START + 45 * DAY).await;
+    process(&mut context, &[claim_rewards(&pool, &pool_address(), &pool.reward_vault)], &[&pool.user])
+        .await
+        .unwrap();
+    assert_eq!(token_balance(&mut context, &pool.user_token_account).await, USER_BALANCE + 2_465);
+}
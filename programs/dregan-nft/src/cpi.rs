@@ -0,0 +1,187 @@
+// DREGAN Access CPI helpers - gate another program's instruction on a DREGAN tier
+// Build with `dregan-nft = { features = ["no-entrypoint"] }` to link these in
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::AccountInfo,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program::{get_return_data, invoke},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use crate::{
+    AccessInstruction, AccessTier, ACCESS_SEED, CONFIG_SEED, FEATURE_REGISTRY_SEED, WALLET_LINK_SEED,
+};
+
+/// The access config PDA; the program accepts no other config
+pub fn config_address() -> Pubkey {
+    Pubkey::find_program_address(&[CONFIG_SEED], &crate::id()).0
+}
+
+/// Access account PDA for `owner`
+pub fn access_account_address(owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[ACCESS_SEED, owner.as_ref()], &crate::id()).0
+}
+
+/// Wallet link PDA for a secondary wallet
+pub fn wallet_link_address(secondary: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[WALLET_LINK_SEED, secondary.as_ref()], &crate::id()).0
+}
+
+/// Accounts identifying `owner`'s access: their own access account, or for a
+/// linked secondary wallet, the primary's access account plus the wallet link
+fn access_metas(owner: &Pubkey, primary: Option<&Pubkey>) -> (AccountMeta, Option<AccountMeta>) {
+    match primary {
+        Some(primary) => (
+            AccountMeta::new_readonly(access_account_address(primary), false),
+            Some(AccountMeta::new_readonly(wallet_link_address(owner), false)),
+        ),
+        None => (AccountMeta::new_readonly(access_account_address(owner), false), None),
+    }
+}
+
+/// Feature registry PDA for the access config
+pub fn feature_registry_address() -> Pubkey {
+    Pubkey::find_program_address(&[FEATURE_REGISTRY_SEED, config_address().as_ref()], &crate::id()).0
+}
+
+/// `RequireTier` instruction for `owner`; pass `primary` when `owner` is a
+/// linked secondary wallet
+pub fn require_tier_instruction(
+    owner: &Pubkey,
+    primary: Option<&Pubkey>,
+    min_tier: AccessTier,
+) -> Result<Instruction, ProgramError> {
+    let (access_meta, link_meta) = access_metas(owner, primary);
+    let mut accounts = vec![
+        access_meta,
+        AccountMeta::new_readonly(*owner, false),
+        AccountMeta::new_readonly(config_address(), false),
+    ];
+    accounts.extend(link_meta);
+    Ok(Instruction {
+        program_id: crate::id(),
+        accounts,
+        data: AccessInstruction::RequireTier { min_tier }.try_to_vec()?,
+    })
+}
+
+/// `HasFeature` instruction for `owner` against the feature registry; pass
+/// `primary` when `owner` is a linked secondary wallet
+pub fn has_feature_instruction(
+    owner: &Pubkey,
+    primary: Option<&Pubkey>,
+    feature_id: u8,
+) -> Result<Instruction, ProgramError> {
+    let (access_meta, link_meta) = access_metas(owner, primary);
+    let mut accounts = vec![
+        access_meta,
+        AccountMeta::new_readonly(*owner, false),
+        AccountMeta::new_readonly(config_address(), false),
+        AccountMeta::new_readonly(feature_registry_address(), false),
+    ];
+    accounts.extend(link_meta);
+    Ok(Instruction {
+        program_id: crate::id(),
+        accounts,
+        data: AccessInstruction::HasFeature { feature_id }.try_to_vec()?,
+    })
+}
+
+/// CPI into `RequireTier`: errors unless `owner` holds at least `min_tier`
+/// (non-stale), otherwise returns their effective tier. `config_account` must be
+/// the config PDA. For a linked secondary wallet pass the primary's access
+/// account and the secondary's wallet link.
+pub fn require_tier<'a>(
+    dregan_nft_program: &AccountInfo<'a>,
+    access_account: &AccountInfo<'a>,
+    owner: &AccountInfo<'a>,
+    config_account: &AccountInfo<'a>,
+    wallet_link: Option<&AccountInfo<'a>>,
+    min_tier: AccessTier,
+) -> Result<AccessTier, ProgramError> {
+    check_address(config_account, &config_address(), "access config")?;
+    let data = AccessInstruction::RequireTier { min_tier }.try_to_vec()?;
+    invoke_access_check(
+        dregan_nft_program,
+        &[access_account, owner, config_account],
+        wallet_link,
+        data,
+    )
+}
+
+/// CPI into `HasFeature`: errors unless `owner`'s effective tier is entitled
+/// to `feature_id`, otherwise returns that tier. `config_account` and
+/// `registry_account` must be the config and feature registry PDAs. Linked
+/// wallets as for [`require_tier`].
+pub fn has_feature<'a>(
+    dregan_nft_program: &AccountInfo<'a>,
+    access_account: &AccountInfo<'a>,
+    owner: &AccountInfo<'a>,
+    config_account: &AccountInfo<'a>,
+    registry_account: &AccountInfo<'a>,
+    wallet_link: Option<&AccountInfo<'a>>,
+    feature_id: u8,
+) -> Result<AccessTier, ProgramError> {
+    check_address(config_account, &config_address(), "access config")?;
+    check_address(registry_account, &feature_registry_address(), "feature registry")?;
+    let data = AccessInstruction::HasFeature { feature_id }.try_to_vec()?;
+    invoke_access_check(
+        dregan_nft_program,
+        &[access_account, owner, config_account, registry_account],
+        wallet_link,
+        data,
+    )
+}
+
+fn check_address(account: &AccountInfo, expected: &Pubkey, name: &str) -> Result<(), ProgramError> {
+    if account.key != expected {
+        msg!("Invalid {}: expected {}, got {}", name, expected, account.key);
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(())
+}
+
+fn invoke_access_check<'a>(
+    dregan_nft_program: &AccountInfo<'a>,
+    accounts: &[&AccountInfo<'a>],
+    wallet_link: Option<&AccountInfo<'a>>,
+    data: Vec<u8>,
+) -> Result<AccessTier, ProgramError> {
+    if *dregan_nft_program.key != crate::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let mut account_infos: Vec<AccountInfo<'a>> = accounts
+        .iter()
+        .copied()
+        .chain(wallet_link)
+        .cloned()
+        .collect();
+    let instruction = Instruction {
+        program_id: crate::id(),
+        accounts: account_infos
+            .iter()
+            .map(|account| AccountMeta::new_readonly(*account.key, false))
+            .collect(),
+        data,
+    };
+    account_infos.push(dregan_nft_program.clone());
+
+    invoke(&instruction, &account_infos)?;
+    returned_tier()
+}
+
+fn returned_tier() -> Result<AccessTier, ProgramError> {
+    match get_return_data() {
+        Some((program_id, data)) if program_id == crate::id() => {
+            AccessTier::try_from_slice(&data).map_err(|_| ProgramError::InvalidAccountData)
+        }
+        _ => {
+            msg!("DREGAN Access returned no tier");
+            Err(ProgramError::InvalidAccountData)
+        }
+    }
+}
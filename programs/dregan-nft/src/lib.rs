@@ -1,34 +1,131 @@
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
-    entrypoint,
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
     entrypoint::ProgramResult,
+    hash::hashv,
+    log::sol_log_data,
     msg,
+    program::{invoke, invoke_signed, set_return_data},
     program_error::ProgramError,
     program_pack::Pack,
+    program_utils::limited_deserialize,
     pubkey::Pubkey,
     clock::Clock,
-    sysvar::Sysvar,
+    system_instruction,
+    system_program,
+    sysvar::{
+        self,
+        instructions::{load_current_index_checked, load_instruction_at_checked},
+        Sysvar,
+    },
+    rent::Rent,
 };
+#[cfg(not(feature = "no-entrypoint"))]
+use solana_program::entrypoint;
 use borsh::{BorshDeserialize, BorshSerialize};
-use spl_token::state::Account as TokenAccount;
+use dregan_staking::{PoolConfig as StakePoolConfig, StakeAccount, POOL_SEED as STAKE_POOL_SEED, STAKE_SEED};
+use mpl_token_metadata::{
+    accounts::{MasterEdition, Metadata},
+    instructions::{
+        BurnV1CpiBuilder, CreateMasterEditionV3CpiBuilder, CreateMetadataAccountV3CpiBuilder,
+        UpdateMetadataAccountV2CpiBuilder, VerifyCollectionV1CpiBuilder,
+    },
+    types::{Collection, CollectionDetails, Creator, DataV2},
+    MAX_NAME_LENGTH, MAX_SYMBOL_LENGTH, MAX_URI_LENGTH,
+};
+use spl_token::state::{Account as TokenAccount, Mint};
+use spl_token_2022::{
+    extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions},
+    state::Mint as Mint2022,
+};
+use spl_token_metadata_interface::state::{Field, TokenMetadata};
+
+pub mod cpi;
 
 // DREGAN NFT Access Control - Fixed Version with On-Chain Balance Verification
 // Reads actual token balance from chain instead of trusting client input
 
 solana_program::declare_id!("qTSt5stsafLoERpm4j61meXw5ywNnMwgXSDxsiZDJ4C");
 
-// Token thresholds for each tier (in smallest token units, assuming 6 decimals)
-// BASIC: 100 DREGAN (100 * 10^6 = 100_000_000)
-// PRO: 500 DREGAN (500 * 10^6 = 500_000_000)  
-// ELITE: 1000 DREGAN (1000 * 10^6 = 1_000_000_000)
-pub const BASIC_THRESHOLD: u64 = 100_000_000;    // 100 DREGAN
-pub const PRO_THRESHOLD: u64 = 500_000_000;      // 500 DREGAN
-pub const ELITE_THRESHOLD: u64 = 1_000_000_000;  // 1000 DREGAN
+// Default tier thresholds in whole DREGAN; scaled by the mint's decimals when
+// the config is initialized and adjustable afterwards via UpdateThresholds
+// BASIC: 1,000 DREGAN
+// PRO: 5,000 DREGAN
+// ELITE: 25,000 DREGAN
+pub const DEFAULT_BASIC_TOKENS: u64 = 1_000;
+pub const DEFAULT_PRO_TOKENS: u64 = 5_000;
+pub const DEFAULT_ELITE_TOKENS: u64 = 25_000;
+
+// A verified tier is trusted for this long before it must be re-verified
+pub const DEFAULT_VERIFICATION_VALIDITY: i64 = 7 * 24 * 60 * 60; // 7 days
+
+// Slots a higher balance must hold across two verifications before the upgrade
+// lands (150 slots is roughly a minute). The single-transaction check alone only
+// stops atomic flash loans; a balance borrowed for a few slots still needs this
+// gap to be caught. 0 grants upgrades on the first verification.
+pub const DEFAULT_VERIFICATION_SLOT_GAP: u64 = 150;
+
+// Tiers follow the time-weighted average balance over this window, capped by
+// the current balance; 0 uses the current balance alone
+pub const DEFAULT_TWAB_WINDOW: i64 = 0;
+
+// Balance checkpoints kept per AccessAccount for the TWAB
+pub const TWAB_CHECKPOINTS: usize = 8;
+
+// The only other program allowed alongside a verification in the same transaction
+pub const COMPUTE_BUDGET_PROGRAM_ID: Pubkey =
+    solana_program::pubkey!("ComputeBudget111111111111111111111111111111");
 
-// Seed for PDA derivation
+// Seeds for PDA derivation
+pub const CONFIG_SEED: &[u8] = b"config";
 pub const ACCESS_SEED: &[u8] = b"access";
+pub const NFT_MINT_SEED: &[u8] = b"nft_mint";
+pub const MINT_AUTHORITY_SEED: &[u8] = b"mint_authority";
+pub const COLLECTION_SEED: &[u8] = b"collection";
+pub const BOUNTY_VAULT_SEED: &[u8] = b"bounty_vault";
+pub const FEATURE_REGISTRY_SEED: &[u8] = b"feature_registry";
+pub const USAGE_SEED: &[u8] = b"usage";
+pub const WALLET_LINK_SEED: &[u8] = b"wallet_link";
+
+// Secondary wallets an access account can delegate its tier to
+pub const MAX_LINKED_WALLETS: usize = 3;
+
+// Platform feature ids in the FeatureRegistry; ids up to MAX_FEATURES - 1 can
+// be assigned by the authority later
+pub const MAX_FEATURES: usize = 32;
+pub const FEATURE_LAUNCH_MONITOR: u8 = 0;
+pub const FEATURE_BASIC_ALERTS: u8 = 1;
+pub const FEATURE_SNIPER_BOT: u8 = 2;
+pub const FEATURE_HONEYPOT_DETECTION: u8 = 3;
+pub const FEATURE_AI_HUB: u8 = 4;
+pub const FEATURE_CHART_ORACLE: u8 = 5;
+pub const FEATURE_BOT_BUILDER: u8 = 6;
+pub const FEATURE_CUSTOM_BOTS: u8 = 7;
+
+// Metered usage resets every period, counted from the UsageAccount's creation
+pub const USAGE_PERIOD: i64 = 30 * 24 * 60 * 60; // 30 days
+
+// Custom bots per period for Basic, Pro and Elite, as promised in nft-metadata/
+pub const DEFAULT_CUSTOM_BOT_QUOTAS: [u32; 3] = [5, 25, 100];
+
+// Largest hysteresis band below a tier's threshold, in basis points
+pub const MAX_HYSTERESIS_BPS: u16 = 5_000; // 50%
+
+// Extra mints (LP tokens, staking receipts) counted toward tiers, weighted as
+// DREGAN base units per base unit of the mint, scaled by WEIGHT_SCALE
+pub const MAX_WEIGHTED_MINTS: usize = 4;
+pub const WEIGHT_SCALE: u64 = 1_000_000;
+
+// Length of one paid subscription period
+pub const SUBSCRIPTION_PERIOD: i64 = 30 * 24 * 60 * 60; // 30 days
+
+// DREGAN Access collection NFT that every access NFT is verified into
+pub const COLLECTION_NAME: &str = "DREGAN Access";
+pub const COLLECTION_SYMBOL: &str = "DRGA";
+pub const COLLECTION_URI: &str =
+    "https://raw.githubusercontent.com/nodesforthewin-cpu/dregan-contracts/main/nft-metadata/collection.json";
 
-#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum AccessTier {
     None,
     Basic,
@@ -37,12 +134,13 @@ pub enum AccessTier {
 }
 
 impl AccessTier {
-    pub fn from_balance(balance: u64) -> Self {
-        if balance >= ELITE_THRESHOLD {
+    /// Tier for a balance given `[basic, pro, elite]` thresholds in base units
+    pub fn from_balance(balance: u64, thresholds: &[u64; 3]) -> Self {
+        if balance >= thresholds[2] {
             AccessTier::Elite
-        } else if balance >= PRO_THRESHOLD {
+        } else if balance >= thresholds[1] {
             AccessTier::Pro
-        } else if balance >= BASIC_THRESHOLD {
+        } else if balance >= thresholds[0] {
             AccessTier::Basic
         } else {
             AccessTier::None
@@ -57,6 +155,108 @@ impl AccessTier {
             AccessTier::Elite => 3,
         }
     }
+    
+    /// This tier's bit in a FeatureRegistry tier mask
+    pub fn mask_bit(&self) -> u8 {
+        1 << self.to_u8()
+    }
+    
+    /// Tier mask granting a feature to `self` and every tier above it
+    pub fn and_above_mask(&self) -> u8 {
+        (FeatureRegistry::ALL_TIERS << self.to_u8()) & FeatureRegistry::ALL_TIERS
+    }
+}
+
+/// An extra accepted mint and its DREGAN-equivalent weight; `weight / WEIGHT_SCALE`
+/// DREGAN base units per base unit held. The default mint marks an empty slot.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default)]
+pub struct WeightedMint {
+    pub mint: Pubkey,
+    pub weight: u64,
+}
+
+impl WeightedMint {
+    pub const LEN: usize = 32 + 8;
+}
+
+/// Balance read at a verification; holds until the next checkpoint.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default)]
+pub struct BalanceCheckpoint {
+    pub timestamp: i64,
+    pub balance: u64,
+}
+
+impl BalanceCheckpoint {
+    pub const LEN: usize = 8 + 8;
+}
+
+/// Metaplex name/symbol/URI for one tier, stored zero-padded so AccessConfig
+/// keeps a fixed size.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct TierMetadata {
+    pub name: [u8; MAX_NAME_LENGTH],
+    pub symbol: [u8; MAX_SYMBOL_LENGTH],
+    pub uri: [u8; MAX_URI_LENGTH],
+}
+
+impl TierMetadata {
+    pub const LEN: usize = MAX_NAME_LENGTH + MAX_SYMBOL_LENGTH + MAX_URI_LENGTH; // 242 bytes
+    
+    pub fn new(name: &str, symbol: &str, uri: &str) -> Result<Self, ProgramError> {
+        Ok(TierMetadata {
+            name: pad_field(name)?,
+            symbol: pad_field(symbol)?,
+            uri: pad_field(uri)?,
+        })
+    }
+    
+    pub fn name(&self) -> String {
+        unpad_field(&self.name)
+    }
+    
+    pub fn symbol(&self) -> String {
+        unpad_field(&self.symbol)
+    }
+    
+    pub fn uri(&self) -> String {
+        unpad_field(&self.uri)
+    }
+    
+    /// Defaults matching nft-metadata/{basic,pro,elite}.json
+    pub fn defaults() -> [TierMetadata; 3] {
+        [
+            TierMetadata::new("DREGAN BASIC Access", "DRGB", &format!("{}/basic.json", NFT_METADATA_BASE_URI)),
+            TierMetadata::new("DREGAN PRO Access", "DRGP", &format!("{}/pro.json", NFT_METADATA_BASE_URI)),
+            TierMetadata::new("DREGAN ELITE Access", "DRGE", &format!("{}/elite.json", NFT_METADATA_BASE_URI)),
+        ]
+        .map(|metadata| metadata.expect("default tier metadata fits"))
+    }
+    
+    /// Shown by an access NFT a keeper has revoked until its owner syncs or burns it,
+    /// matching nft-metadata/revoked.json
+    pub fn revoked() -> TierMetadata {
+        TierMetadata::new("DREGAN Access (Revoked)", "DRGX", &format!("{}/revoked.json", NFT_METADATA_BASE_URI))
+            .expect("revoked metadata fits")
+    }
+}
+
+const NFT_METADATA_BASE_URI: &str =
+    "https://raw.githubusercontent.com/nodesforthewin-cpu/dregan-contracts/main/nft-metadata";
+
+fn pad_field<const N: usize>(value: &str) -> Result<[u8; N], ProgramError> {
+    let bytes = value.as_bytes();
+    if bytes.len() > N {
+        msg!("Metadata field too long: {} > {} bytes", bytes.len(), N);
+        return Err(ProgramError::InvalidArgument);
+    }
+    let mut field = [0u8; N];
+    field[..bytes.len()].copy_from_slice(bytes);
+    Ok(field)
+}
+
+fn unpad_field(field: &[u8]) -> String {
+    let end = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Debug)]
@@ -64,11 +264,145 @@ pub struct AccessConfig {
     pub is_initialized: bool,
     pub authority: Pubkey,
     pub token_mint: Pubkey,
+    pub collection_mint: Pubkey,
+    pub tier_metadata: [TierMetadata; 3],
+    pub soulbound: bool,
+    pub token_decimals: u8,
+    pub thresholds: [u64; 3],
+    pub verification_validity: i64,
+    pub verification_slot_gap: u64,
+    pub twab_window: i64,
+    pub refresh_bounty: u64,
+    pub platform_signer: Pubkey,
+    pub subscription_mint: Pubkey,
+    pub subscription_treasury: Pubkey,
+    pub subscription_prices: [u64; 3],
+    pub downgrade_grace_period: i64,
+    pub hysteresis_bps: u16,
+    pub weighted_mints: [WeightedMint; MAX_WEIGHTED_MINTS],
     pub bump: u8,
 }
 
 impl AccessConfig {
-    pub const LEN: usize = 1 + 32 + 32 + 1; // 66 bytes
+    pub const LEN: usize = 1 + 32 + 32 + 32 + 3 * TierMetadata::LEN + 1 + 1 + 8 * 3 + 8 + 8 + 8 + 8 + 32
+        + 32 + 32 + 8 * 3 + 8 + 2 + MAX_WEIGHTED_MINTS * WeightedMint::LEN + 1; // 1172 bytes
+    
+    /// A fresh config with every setting at its default
+    pub fn new(authority: Pubkey, token_mint: Pubkey, token_decimals: u8, bump: u8) -> Result<Self, ProgramError> {
+        Ok(AccessConfig {
+            is_initialized: true,
+            authority,
+            token_mint,
+            collection_mint: Pubkey::default(),
+            tier_metadata: TierMetadata::defaults(),
+            soulbound: false,
+            token_decimals,
+            thresholds: Self::default_thresholds(token_decimals)?,
+            verification_validity: DEFAULT_VERIFICATION_VALIDITY,
+            verification_slot_gap: DEFAULT_VERIFICATION_SLOT_GAP,
+            twab_window: DEFAULT_TWAB_WINDOW,
+            refresh_bounty: 0,
+            platform_signer: Pubkey::default(),
+            subscription_mint: token_mint,
+            subscription_treasury: Pubkey::default(),
+            subscription_prices: [0; 3],
+            downgrade_grace_period: 0,
+            hysteresis_bps: 0,
+            weighted_mints: [WeightedMint::default(); MAX_WEIGHTED_MINTS],
+            bump,
+        })
+    }
+    
+    /// Default thresholds in base units for a mint with `decimals`
+    pub fn default_thresholds(decimals: u8) -> Result<[u64; 3], ProgramError> {
+        let unit = 10u64
+            .checked_pow(decimals as u32)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        let scale = |tokens: u64| tokens.checked_mul(unit).ok_or(ProgramError::ArithmeticOverflow);
+        Ok([
+            scale(DEFAULT_BASIC_TOKENS)?,
+            scale(DEFAULT_PRO_TOKENS)?,
+            scale(DEFAULT_ELITE_TOKENS)?,
+        ])
+    }
+    
+    /// Thresholds must be non-zero and strictly increasing Basic < Pro < Elite
+    pub fn validate_thresholds(thresholds: &[u64; 3]) -> ProgramResult {
+        if thresholds[0] == 0 || thresholds[0] >= thresholds[1] || thresholds[1] >= thresholds[2] {
+            msg!("Thresholds must be strictly increasing and non-zero: {:?}", thresholds);
+            return Err(ProgramError::InvalidArgument);
+        }
+        Ok(())
+    }
+    
+    /// Token program new access NFTs are minted under
+    pub fn nft_token_program(&self) -> Pubkey {
+        if self.soulbound {
+            spl_token_2022::id()
+        } else {
+            spl_token::id()
+        }
+    }
+    
+    /// DREGAN-equivalent of `amount` base units of `mint`; None for mints not accepted
+    pub fn dregan_equivalent(&self, mint: &Pubkey, amount: u64) -> Option<u64> {
+        if *mint == self.token_mint {
+            return Some(amount);
+        }
+        self.weighted_mints
+            .iter()
+            .find(|weighted| weighted.mint == *mint && weighted.mint != Pubkey::default())
+            .map(|weighted| {
+                (amount as u128 * weighted.weight as u128 / WEIGHT_SCALE as u128)
+                    .min(u64::MAX as u128) as u64
+            })
+    }
+    
+    /// Price of one subscription period for `tier`; None when not offered
+    pub fn subscription_price(&self, tier: &AccessTier) -> Option<u64> {
+        let price = match tier {
+            AccessTier::None => 0,
+            AccessTier::Basic => self.subscription_prices[0],
+            AccessTier::Pro => self.subscription_prices[1],
+            AccessTier::Elite => self.subscription_prices[2],
+        };
+        (price != 0).then_some(price)
+    }
+    
+    /// Metadata for a holding tier; None has no NFT
+    pub fn metadata_for(&self, tier: &AccessTier) -> Option<&TierMetadata> {
+        match tier {
+            AccessTier::None => None,
+            AccessTier::Basic => Some(&self.tier_metadata[0]),
+            AccessTier::Pro => Some(&self.tier_metadata[1]),
+            AccessTier::Elite => Some(&self.tier_metadata[2]),
+        }
+    }
+    
+    /// Metaplex data for an access NFT of the given tier metadata
+    pub fn nft_data(
+        &self,
+        tier_metadata: &TierMetadata,
+        creator: &Pubkey,
+        collection_verified: bool,
+    ) -> DataV2 {
+        DataV2 {
+            name: tier_metadata.name(),
+            symbol: tier_metadata.symbol(),
+            uri: tier_metadata.uri(),
+            seller_fee_basis_points: 0,
+            creators: Some(vec![Creator {
+                address: *creator,
+                verified: true,
+                share: 100,
+            }]),
+            collection: Some(Collection {
+                verified: collection_verified,
+                key: self.collection_mint,
+            }),
+            uses: None,
+        }
+    }
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Debug)]
@@ -78,32 +412,525 @@ pub struct AccessAccount {
     pub current_tier: AccessTier,
     pub last_verified_balance: u64,
     pub verification_timestamp: i64,
+    pub nft_mint: Pubkey,
+    pub nft_tier: AccessTier,
+    pub nft_generation: u32,
+    pub pending_tier: AccessTier,
+    pub pending_slot: u64,
+    pub checkpoints: [BalanceCheckpoint; TWAB_CHECKPOINTS],
+    pub checkpoint_head: u8,
+    pub holdings_hash: [u8; 32],
+    pub subscription_tier: AccessTier,
+    pub subscription_expiry: i64,
+    pub downgrade_pending_since: i64,
+    pub linked_wallets: [Pubkey; MAX_LINKED_WALLETS],
+    pub granted_tier: AccessTier,
+    pub grant_expiry: i64,
     pub bump: u8,
 }
 
 impl AccessAccount {
+    pub const LEN: usize = 1 + 32 + 1 + 8 + 8 + 32 + 1 + 4 + 1 + 8
+        + TWAB_CHECKPOINTS * BalanceCheckpoint::LEN + 1 + 32 + 1 + 8 + 8
+        + 32 * MAX_LINKED_WALLETS + 1 + 8 + 1; // 380 bytes
+    
+    /// True once the last verification is older than the config's validity window
+    pub fn is_stale(&self, config: &AccessConfig, current_time: i64) -> bool {
+        current_time.saturating_sub(self.verification_timestamp) > config.verification_validity
+    }
+    
+    /// Holding-based tier: the stored tier, or None once it has gone stale
+    pub fn holding_tier(&self, config: &AccessConfig, current_time: i64) -> AccessTier {
+        if self.is_stale(config, current_time) {
+            AccessTier::None
+        } else {
+            self.current_tier.clone()
+        }
+    }
+    
+    /// Paid tier, until its subscription expires
+    pub fn subscribed_tier(&self, current_time: i64) -> AccessTier {
+        if current_time < self.subscription_expiry {
+            self.subscription_tier.clone()
+        } else {
+            AccessTier::None
+        }
+    }
+    
+    /// Complimentary tier from the authority, until the grant expires
+    pub fn complimentary_tier(&self, current_time: i64) -> AccessTier {
+        if current_time < self.grant_expiry {
+            self.granted_tier.clone()
+        } else {
+            AccessTier::None
+        }
+    }
+    
+    /// Tier to grant right now: the highest of the holding, subscription and
+    /// granted tiers. Off-chain services and CPI callers should gate on this,
+    /// not `current_tier`.
+    pub fn effective_tier(&self, config: &AccessConfig, current_time: i64) -> AccessTier {
+        self.holding_tier(config, current_time)
+            .max(self.subscribed_tier(current_time))
+            .max(self.complimentary_tier(current_time))
+    }
+    
+    /// Checkpoint a balance read and return the balance tiers are computed on:
+    /// the TWAB over `config.twab_window`, never above the current balance.
+    /// Reads closer together than window / (TWAB_CHECKPOINTS - 1), and never less
+    /// than a second apart, fold into the latest checkpoint at the lower balance,
+    /// so the ring always spans the window.
+    pub fn record_balance(&mut self, balance: u64, config: &AccessConfig, current_time: i64) -> u64 {
+        let spacing = (config.twab_window / (TWAB_CHECKPOINTS as i64 - 1)).max(1);
+        let latest = (self.checkpoint_head as usize + TWAB_CHECKPOINTS - 1) % TWAB_CHECKPOINTS;
+        let checkpoint = &mut self.checkpoints[latest];
+        if checkpoint.timestamp != 0 && current_time.saturating_sub(checkpoint.timestamp) < spacing {
+            checkpoint.balance = checkpoint.balance.min(balance);
+        } else {
+            self.checkpoints[self.checkpoint_head as usize] = BalanceCheckpoint {
+                timestamp: current_time,
+                balance,
+            };
+            self.checkpoint_head = ((self.checkpoint_head as usize + 1) % TWAB_CHECKPOINTS) as u8;
+        }
+        
+        if config.twab_window == 0 {
+            return balance;
+        }
+        self.twab(config.twab_window, current_time).min(balance)
+    }
+    
+    /// Time-weighted average balance over the `window` seconds before `current_time`;
+    /// time before the oldest checkpoint counts as a zero balance.
+    pub fn twab(&self, window: i64, current_time: i64) -> u64 {
+        if window <= 0 {
+            return 0;
+        }
+        let window_start = current_time.saturating_sub(window);
+        let mut weighted: u128 = 0;
+        for offset in 0..TWAB_CHECKPOINTS {
+            let index = (self.checkpoint_head as usize + offset) % TWAB_CHECKPOINTS;
+            let checkpoint = &self.checkpoints[index];
+            if checkpoint.timestamp == 0 {
+                continue;
+            }
+            // Each balance holds until the next (chronologically later) checkpoint
+            let segment_end = (offset + 1..TWAB_CHECKPOINTS)
+                .map(|next| &self.checkpoints[(self.checkpoint_head as usize + next) % TWAB_CHECKPOINTS])
+                .find(|next| next.timestamp != 0)
+                .map_or(current_time, |next| next.timestamp);
+            let segment_start = checkpoint.timestamp.max(window_start);
+            if segment_end > segment_start {
+                weighted += checkpoint.balance as u128 * (segment_end - segment_start) as u128;
+            }
+        }
+        (weighted / window as u128) as u64
+    }
+    
+    /// Tier a reading should move to once the downgrade policy is applied. Within
+    /// `hysteresis_bps` below the current tier's threshold nothing drops; past it
+    /// the current tier is held for `downgrade_grace_period` from the first low
+    /// reading (tracked in `downgrade_pending_since`). Rises clear the timer.
+    pub fn soften_downgrade(
+        &mut self,
+        balance: u64,
+        balance_tier: AccessTier,
+        current: &AccessTier,
+        config: &AccessConfig,
+        current_time: i64,
+    ) -> AccessTier {
+        if balance_tier >= *current {
+            self.downgrade_pending_since = 0;
+            return balance_tier;
+        }
+        
+        let bps = config.hysteresis_bps as u128;
+        let band_thresholds = config
+            .thresholds
+            .map(|threshold| (threshold as u128 * (10_000 - bps) / 10_000) as u64);
+        let target = AccessTier::from_balance(balance, &band_thresholds).min(current.clone());
+        if target == *current {
+            self.downgrade_pending_since = 0;
+            return target;
+        }
+        
+        if config.downgrade_grace_period == 0 {
+            return target;
+        }
+        if self.downgrade_pending_since == 0 {
+            self.downgrade_pending_since = current_time;
+        }
+        if current_time.saturating_sub(self.downgrade_pending_since) >= config.downgrade_grace_period {
+            self.downgrade_pending_since = 0;
+            target
+        } else {
+            msg!(
+                "Holding {:?} through downgrade grace period until {}",
+                current,
+                self.downgrade_pending_since.saturating_add(config.downgrade_grace_period)
+            );
+            current.clone()
+        }
+    }
+    
+    /// Record a verification reading `balance_tier` and return the tier it grants.
+    /// Drops apply at once. With a slot gap configured, a rise is held as pending
+    /// and only granted by a later verification at least that many slots on,
+    /// capped at the lower of the two readings.
+    pub fn apply_verification(
+        &mut self,
+        balance: u64,
+        balance_tier: AccessTier,
+        config: &AccessConfig,
+        clock: &Clock,
+    ) -> AccessTier {
+        let current = self.holding_tier(config, clock.unix_timestamp);
+        let balance_tier = self.soften_downgrade(balance, balance_tier, &current, config, clock.unix_timestamp);
+        let granted = if config.verification_slot_gap == 0 || balance_tier <= current {
+            self.pending_tier = AccessTier::None;
+            self.pending_slot = 0;
+            balance_tier
+        } else if self.pending_tier != AccessTier::None
+            && clock.slot.saturating_sub(self.pending_slot) >= config.verification_slot_gap
+        {
+            let confirmed = balance_tier.min(self.pending_tier.clone()).max(current);
+            self.pending_tier = AccessTier::None;
+            self.pending_slot = 0;
+            confirmed
+        } else {
+            if self.pending_tier == AccessTier::None {
+                self.pending_slot = clock.slot;
+                self.pending_tier = balance_tier;
+            } else {
+                self.pending_tier = balance_tier.min(self.pending_tier.clone());
+            }
+            msg!(
+                "Upgrade to {:?} pending until slot {}",
+                self.pending_tier,
+                self.pending_slot.saturating_add(config.verification_slot_gap)
+            );
+            current
+        };
+        
+        self.current_tier = granted.clone();
+        self.last_verified_balance = balance;
+        self.verification_timestamp = clock.unix_timestamp;
+        granted
+    }
+}
+
+/// Feature id -> tier mask table shared by the backend and on-chain consumers.
+/// Bit `n` of a mask grants the feature to the tier with `to_u8() == n`; a zero
+/// mask means the feature is not offered. Quotas are per-period usage limits
+/// indexed the same way, with 0 meaning unmetered.
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct FeatureRegistry {
+    pub is_initialized: bool,
+    pub config: Pubkey,
+    pub feature_tiers: [u8; MAX_FEATURES],
+    pub feature_quotas: [[u32; 4]; MAX_FEATURES],
+    pub bump: u8,
+}
+
+impl FeatureRegistry {
+    pub const LEN: usize = 1 + 32 + MAX_FEATURES + MAX_FEATURES * 4 * 4 + 1; // 578 bytes
+    pub const ALL_TIERS: u8 = 0b1111;
+    
+    /// The tier/feature table from the README
+    pub fn default_feature_tiers() -> [u8; MAX_FEATURES] {
+        let mut feature_tiers = [0u8; MAX_FEATURES];
+        for feature_id in [FEATURE_LAUNCH_MONITOR, FEATURE_BASIC_ALERTS, FEATURE_CUSTOM_BOTS] {
+            feature_tiers[feature_id as usize] = AccessTier::Basic.and_above_mask();
+        }
+        for feature_id in [FEATURE_SNIPER_BOT, FEATURE_HONEYPOT_DETECTION] {
+            feature_tiers[feature_id as usize] = AccessTier::Pro.and_above_mask();
+        }
+        for feature_id in [FEATURE_AI_HUB, FEATURE_CHART_ORACLE, FEATURE_BOT_BUILDER] {
+            feature_tiers[feature_id as usize] = AccessTier::Elite.and_above_mask();
+        }
+        feature_tiers
+    }
+    
+    /// Custom bot quotas from the NFT metadata; everything else is unmetered
+    pub fn default_feature_quotas() -> [[u32; 4]; MAX_FEATURES] {
+        let mut feature_quotas = [[0u32; 4]; MAX_FEATURES];
+        let [basic, pro, elite] = DEFAULT_CUSTOM_BOT_QUOTAS;
+        feature_quotas[FEATURE_CUSTOM_BOTS as usize] = [0, basic, pro, elite];
+        feature_quotas
+    }
+    
+    /// Per-period quota for `tier` on `feature_id`; 0 is unmetered
+    pub fn quota(&self, feature_id: u8, tier: &AccessTier) -> u32 {
+        self.feature_quotas
+            .get(feature_id as usize)
+            .map_or(0, |quotas| quotas[tier.to_u8() as usize])
+    }
+    
+    /// Whether `tier` is entitled to `feature_id`; unknown ids grant nothing
+    pub fn has_feature(&self, feature_id: u8, tier: &AccessTier) -> bool {
+        self.feature_tiers
+            .get(feature_id as usize)
+            .is_some_and(|mask| mask & tier.mask_bit() != 0)
+    }
+}
+
+/// Secondary wallet -> primary access owner; one per secondary, so a hot wallet
+/// can only ever act for a single primary
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct WalletLink {
+    pub is_initialized: bool,
+    pub secondary: Pubkey,
+    pub primary: Pubkey,
+    pub bump: u8,
+}
+
+impl WalletLink {
+    pub const LEN: usize = 1 + 32 + 32 + 1; // 66 bytes
+}
+
+/// Per-user metered usage for the current USAGE_PERIOD, per feature id
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct UsageAccount {
+    pub is_initialized: bool,
+    pub owner: Pubkey,
+    pub period_start: i64,
+    pub used: [u32; MAX_FEATURES],
+    pub bump: u8,
+}
+
+impl UsageAccount {
+    pub const LEN: usize = 1 + 32 + 8 + MAX_FEATURES * 4 + 1; // 170 bytes
+    
+    /// Move to the period containing `current_time`, clearing usage when a
+    /// boundary has passed. Periods stay aligned to the original start.
+    pub fn roll_period(&mut self, current_time: i64) {
+        let elapsed = current_time.saturating_sub(self.period_start);
+        if elapsed >= USAGE_PERIOD {
+            self.period_start += elapsed / USAGE_PERIOD * USAGE_PERIOD;
+            self.used = [0; MAX_FEATURES];
+        }
+    }
+}
+
+/// AccessConfig as written before NFT, verification and subscription settings existed,
+/// when the config could live at any address. Only read by MigrateConfig.
+#[derive(BorshDeserialize, Debug)]
+pub struct LegacyAccessConfig {
+    pub is_initialized: bool,
+    pub authority: Pubkey,
+    pub token_mint: Pubkey,
+    pub bump: u8,
+}
+
+impl LegacyAccessConfig {
+    pub const LEN: usize = 1 + 32 + 32 + 1; // 66 bytes
+}
+
+/// AccessAccount as written before NFTs, TWAB checkpoints, subscriptions, links and
+/// grants were tracked. Only read by MigrateAccess.
+#[derive(BorshDeserialize, Debug)]
+pub struct LegacyAccessAccount {
+    pub is_initialized: bool,
+    pub owner: Pubkey,
+    pub current_tier: AccessTier,
+    pub last_verified_balance: u64,
+    pub verification_timestamp: i64,
+    pub bump: u8,
+}
+
+impl LegacyAccessAccount {
     pub const LEN: usize = 1 + 32 + 1 + 8 + 8 + 1; // 51 bytes
 }
 
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub enum AccessEvent {
+    TierGranted {
+        authority: Pubkey,
+        user: Pubkey,
+        tier: AccessTier,
+        expires_at: i64,
+    },
+    GrantRevoked {
+        authority: Pubkey,
+        user: Pubkey,
+        tier: AccessTier,
+    },
+}
+
+impl AccessEvent {
+    /// Emit the event as base64 program data in the transaction logs
+    pub fn emit(&self) -> ProgramResult {
+        sol_log_data(&[&self.try_to_vec()?]);
+        Ok(())
+    }
+}
+
+// Append new variants at the end: the borsh tag is the variant index clients already encode
 #[derive(BorshSerialize, BorshDeserialize, Debug)]
 pub enum AccessInstruction {
-    /// Initialize the access control config
-    /// Accounts: [config_account, authority, token_mint]
+    /// Create the access control config at its PDA ([CONFIG_SEED]); there is only one and it
+    /// cannot be re-initialized
+    /// Accounts: [config_account, authority, token_mint, system_program]
     InitializeConfig { bump: u8 },
     
     /// Initialize a user access account
     /// Accounts: [access_account, owner, system_program]
     InitializeAccess { bump: u8 },
     
-    /// Verify user's access tier by reading their actual token balance
-    /// Accounts: [access_account, owner, user_token_account, config_account]
+    /// Verify user's access tier from the summed DREGAN-equivalent balance of their token
+    /// accounts (DREGAN and any weighted mints), plus their dregan-staking position when
+    /// passed as a trailing `stake_account, stake_pool_config` pair.
+    /// Must be a top-level instruction in a transaction with no other programs (compute budget aside).
+    /// Accounts: [access_account, owner, user_token_account, config_account, sysvar_instructions, ...extra_token_accounts, (stake_account, stake_pool_config)]
     VerifyAccess,
     
-    /// Check current tier (read-only)
-    /// Accounts: [access_account]
+    /// Check current tier (read-only); reports None once the verification is stale.
+    /// Pass a secondary's wallet link to check the tier it resolves to.
+    /// Accounts: [access_account, config_account, (wallet_link)]
     CheckTier,
+    
+    /// Verify balance and mint a supply-1 access NFT with tier metadata, verified into the collection.
+    /// With `soulbound` set, mints under Token-2022 (NonTransferable, metadata in the mint via
+    /// MetadataPointer) instead; the Metaplex and collection accounts are then ignored.
+    /// Accounts: [access_account, owner, user_token_account, config_account, nft_mint, owner_nft_token_account, mint_authority, metadata, master_edition, collection_mint, collection_metadata, collection_master_edition, system_program, token_program, associated_token_program, token_metadata_program, sysvar_instructions, ...extra_token_accounts, (stake_account, stake_pool_config)]
+    MintAccessNft,
+    
+    /// Create the DREGAN Access collection NFT (authority only)
+    /// Accounts: [config_account, authority, collection_mint, authority_collection_token_account, mint_authority, collection_metadata, collection_master_edition, system_program, token_program, associated_token_program, token_metadata_program]
+    CreateCollection,
+    
+    /// Set name/symbol/URI used for a tier's access NFTs (authority only)
+    /// Accounts: [config_account, authority]
+    SetTierMetadata { tier: AccessTier, name: String, symbol: String, uri: String },
+    
+    /// Re-verify balance and bring the access NFT in line: metadata follows tier changes,
+    /// dropping to None burns it (a later mint uses the next generation's mint PDA)
+    /// Accounts: [access_account, owner, user_token_account, config_account, nft_mint, owner_nft_token_account, mint_authority, metadata, master_edition, collection_metadata, system_program, token_program, token_metadata_program, sysvar_instructions, ...extra_token_accounts, (stake_account, stake_pool_config)]
+    SyncNft,
+    
+    /// Toggle minting new access NFTs as non-transferable Token-2022 mints (authority only)
+    /// Accounts: [config_account, authority]
+    SetSoulbound { enabled: bool },
+    
+    /// Set `[basic, pro, elite]` tier thresholds in base units (authority only)
+    /// Accounts: [config_account, authority]
+    UpdateThresholds { thresholds: [u64; 3] },
+    
+    /// Set how many seconds a verification stays valid (authority only)
+    /// Accounts: [config_account, authority]
+    SetVerificationValidity { seconds: i64 },
+    
+    /// Set the slot gap a tier upgrade must hold across two verifications; 0 disables (authority only)
+    /// Accounts: [config_account, authority]
+    SetVerificationSlotGap { slots: u64 },
+    
+    /// Set the TWAB window tiers are computed over; 0 uses the current balance (authority only)
+    /// Accounts: [config_account, authority]
+    SetTwabWindow { seconds: i64 },
+    
+    /// Permissionless crank: re-read the owner's holdings over the same account set as their
+    /// last verification and lower (never raise) the stored tier; stale tiers drop to None.
+    /// Pays the keeper `refresh_bounty` lamports from the config's bounty vault PDA
+    /// ([BOUNTY_VAULT_SEED, config_account]) when the tier drops.
+    /// Accounts: [access_account, user_token_account, config_account, keeper, bounty_vault, system_program, ...extra_token_accounts, (stake_account, stake_pool_config)]
+    RefreshAccess,
+    
+    /// Set the lamports paid from the bounty vault per RefreshAccess downgrade; 0 disables (authority only)
+    /// Accounts: [config_account, authority]
+    SetRefreshBounty { lamports: u64 },
+    
+    /// Fail unless the owner's non-stale tier is at least `min_tier`; the effective tier is
+    /// returned (borsh `AccessTier`) via return data. Meant for CPI, see the `cpi` module.
+    /// A linked secondary wallet passes its wallet link and the primary's access account.
+    /// Accounts: [access_account, owner, config_account, (wallet_link)]
+    RequireTier { min_tier: AccessTier },
+    
+    /// Create the feature registry for a config, seeded with the README tier/feature table (authority only)
+    /// Accounts: [registry_account, config_account, authority, system_program]
+    InitializeFeatureRegistry { bump: u8 },
+    
+    /// Set which tiers get a feature as a tier bitmask; 0 withdraws it (authority only)
+    /// Accounts: [registry_account, config_account, authority]
+    SetFeatureTiers { feature_id: u8, tier_mask: u8 },
+    
+    /// Fail unless the owner's non-stale tier is entitled to `feature_id`; the effective tier
+    /// is returned (borsh `AccessTier`) via return data
+    /// Accounts: [access_account, owner, config_account, registry_account, (wallet_link)]
+    HasFeature { feature_id: u8 },
+    
+    /// Set a feature's per-period quotas for `[none, basic, pro, elite]`; 0 is unmetered (authority only)
+    /// Accounts: [registry_account, config_account, authority]
+    SetFeatureQuotas { feature_id: u8, quotas: [u32; 4] },
+    
+    /// Set the platform signer allowed to consume usage quotas (authority only)
+    /// Accounts: [config_account, authority]
+    SetPlatformSigner { signer: Pubkey },
+    
+    /// Initialize a user's usage account; its first period starts now
+    /// Accounts: [usage_account, owner, system_program]
+    InitializeUsage { bump: u8 },
+    
+    /// Record `units` of metered use of `feature_id` against the owner's tier quota for the
+    /// current 30-day period (platform signer only); remaining units (u32) are returned via return data
+    /// Accounts: [usage_account, access_account, config_account, registry_account, platform_signer]
+    ConsumeQuota { feature_id: u8, units: u32 },
+    
+    /// Set subscription payment terms: the payment mint (DREGAN or e.g. USDC), the treasury
+    /// token account payments go to (default pubkey burns them instead) and the per-period
+    /// price for `[basic, pro, elite]`, 0 meaning not offered (authority only)
+    /// Accounts: [config_account, authority]
+    SetSubscriptionTerms { mint: Pubkey, treasury: Pubkey, prices: [u64; 3] },
+    
+    /// Pay for `periods` 30-day periods of `tier`, extending an active subscription of the same
+    /// tier. Payment goes to the treasury, or is burned when no treasury is set.
+    /// Accounts: [access_account, owner, payer_token_account, config_account, treasury_or_mint, token_program]
+    Subscribe { tier: AccessTier, periods: u32 },
+    
+    /// Set the downgrade policy: seconds a tier is held after the balance first drops below it,
+    /// and a hysteresis band in bps below each threshold that does not count as a drop (authority only)
+    /// Accounts: [config_account, authority]
+    SetDowngradePolicy { grace_period: i64, hysteresis_bps: u16 },
+    
+    /// Delegate the owner's tier to a secondary wallet, which must co-sign to accept. The owner
+    /// pays for the wallet link PDA the first time; an unlinked one is reused.
+    /// Accounts: [access_account, owner, secondary, wallet_link, system_program]
+    LinkWallet { bump: u8 },
+    
+    /// Remove a secondary wallet; either the owner or the secondary may sign
+    /// Accounts: [access_account, signer, wallet_link]
+    UnlinkWallet,
+    
+    /// Grant `user` a complimentary tier until `expires_at`, replacing any earlier grant;
+    /// emits `AccessEvent::TierGranted` (authority only)
+    /// Accounts: [access_account, config_account, authority]
+    GrantTier { user: Pubkey, tier: AccessTier, expires_at: i64 },
+    
+    /// Revoke `user`'s complimentary tier; emits `AccessEvent::GrantRevoked` (authority only)
+    /// Accounts: [access_account, config_account, authority]
+    RevokeGrant { user: Pubkey },
+    
+    /// Set weighted mint slot `index` (LP tokens, staking receipts): `weight / 1_000_000` DREGAN
+    /// base units per base unit of `mint`; a zero weight clears the slot (authority only)
+    /// Accounts: [config_account, authority]
+    SetWeightedMint { index: u8, mint: Pubkey, weight: u64 },
+    
+    /// Permissionless keeper path: lower the owner's access NFT to their stored holding tier (run
+    /// RefreshAccess first to re-read holdings), or to the revoked metadata once that tier is None.
+    /// Signed by the mint authority PDA; only the owner's SyncNft raises it again or burns it.
+    /// Accounts: [access_account, config_account, keeper, nft_mint, mint_authority, metadata, system_program, token_program, token_metadata_program]
+    RefreshNft,
+    
+    /// Move a config created at an arbitrary address to the config PDA with default settings
+    /// and close the old account (program upgrade authority only)
+    /// Accounts: [legacy_config, config_account, upgrade_authority, program_data, token_mint, system_program]
+    MigrateConfig { bump: u8 },
+    
+    /// Grow a legacy 51-byte access account to the current layout (permissionless)
+    /// Accounts: [access_account, payer, system_program]
+    MigrateAccess,
 }
 
+#[cfg(not(feature = "no-entrypoint"))]
 entrypoint!(process_instruction);
 
 pub fn process_instruction(
@@ -131,6 +958,126 @@ pub fn process_instruction(
             msg!("DREGAN Access: Check Tier");
             process_check_tier(program_id, accounts)
         }
+        AccessInstruction::MintAccessNft => {
+            msg!("DREGAN Access: Mint Access NFT");
+            process_mint_access_nft(program_id, accounts)
+        }
+        AccessInstruction::CreateCollection => {
+            msg!("DREGAN Access: Create Collection");
+            process_create_collection(program_id, accounts)
+        }
+        AccessInstruction::SetTierMetadata { tier, name, symbol, uri } => {
+            msg!("DREGAN Access: Set Tier Metadata");
+            process_set_tier_metadata(program_id, accounts, tier, name, symbol, uri)
+        }
+        AccessInstruction::SyncNft => {
+            msg!("DREGAN Access: Sync NFT");
+            process_sync_nft(program_id, accounts)
+        }
+        AccessInstruction::SetSoulbound { enabled } => {
+            msg!("DREGAN Access: Set Soulbound");
+            process_set_soulbound(program_id, accounts, enabled)
+        }
+        AccessInstruction::UpdateThresholds { thresholds } => {
+            msg!("DREGAN Access: Update Thresholds");
+            process_update_thresholds(program_id, accounts, thresholds)
+        }
+        AccessInstruction::SetVerificationValidity { seconds } => {
+            msg!("DREGAN Access: Set Verification Validity");
+            process_set_verification_validity(program_id, accounts, seconds)
+        }
+        AccessInstruction::SetVerificationSlotGap { slots } => {
+            msg!("DREGAN Access: Set Verification Slot Gap");
+            process_set_verification_slot_gap(program_id, accounts, slots)
+        }
+        AccessInstruction::SetTwabWindow { seconds } => {
+            msg!("DREGAN Access: Set TWAB Window");
+            process_set_twab_window(program_id, accounts, seconds)
+        }
+        AccessInstruction::RefreshAccess => {
+            msg!("DREGAN Access: Refresh Access");
+            process_refresh_access(program_id, accounts)
+        }
+        AccessInstruction::SetRefreshBounty { lamports } => {
+            msg!("DREGAN Access: Set Refresh Bounty");
+            process_set_refresh_bounty(program_id, accounts, lamports)
+        }
+        AccessInstruction::RequireTier { min_tier } => {
+            msg!("DREGAN Access: Require Tier");
+            process_require_tier(program_id, accounts, min_tier)
+        }
+        AccessInstruction::InitializeFeatureRegistry { bump } => {
+            msg!("DREGAN Access: Initialize Feature Registry");
+            process_initialize_feature_registry(program_id, accounts, bump)
+        }
+        AccessInstruction::SetFeatureTiers { feature_id, tier_mask } => {
+            msg!("DREGAN Access: Set Feature Tiers");
+            process_set_feature_tiers(program_id, accounts, feature_id, tier_mask)
+        }
+        AccessInstruction::HasFeature { feature_id } => {
+            msg!("DREGAN Access: Has Feature");
+            process_has_feature(program_id, accounts, feature_id)
+        }
+        AccessInstruction::SetFeatureQuotas { feature_id, quotas } => {
+            msg!("DREGAN Access: Set Feature Quotas");
+            process_set_feature_quotas(program_id, accounts, feature_id, quotas)
+        }
+        AccessInstruction::SetPlatformSigner { signer } => {
+            msg!("DREGAN Access: Set Platform Signer");
+            process_set_platform_signer(program_id, accounts, signer)
+        }
+        AccessInstruction::InitializeUsage { bump } => {
+            msg!("DREGAN Access: Initialize Usage Account");
+            process_initialize_usage(program_id, accounts, bump)
+        }
+        AccessInstruction::ConsumeQuota { feature_id, units } => {
+            msg!("DREGAN Access: Consume Quota");
+            process_consume_quota(program_id, accounts, feature_id, units)
+        }
+        AccessInstruction::SetSubscriptionTerms { mint, treasury, prices } => {
+            msg!("DREGAN Access: Set Subscription Terms");
+            process_set_subscription_terms(program_id, accounts, mint, treasury, prices)
+        }
+        AccessInstruction::Subscribe { tier, periods } => {
+            msg!("DREGAN Access: Subscribe");
+            process_subscribe(program_id, accounts, tier, periods)
+        }
+        AccessInstruction::SetDowngradePolicy { grace_period, hysteresis_bps } => {
+            msg!("DREGAN Access: Set Downgrade Policy");
+            process_set_downgrade_policy(program_id, accounts, grace_period, hysteresis_bps)
+        }
+        AccessInstruction::LinkWallet { bump } => {
+            msg!("DREGAN Access: Link Wallet");
+            process_link_wallet(program_id, accounts, bump)
+        }
+        AccessInstruction::UnlinkWallet => {
+            msg!("DREGAN Access: Unlink Wallet");
+            process_unlink_wallet(program_id, accounts)
+        }
+        AccessInstruction::GrantTier { user, tier, expires_at } => {
+            msg!("DREGAN Access: Grant Tier");
+            process_grant_tier(program_id, accounts, user, Some((tier, expires_at)))
+        }
+        AccessInstruction::RevokeGrant { user } => {
+            msg!("DREGAN Access: Revoke Grant");
+            process_grant_tier(program_id, accounts, user, None)
+        }
+        AccessInstruction::SetWeightedMint { index, mint, weight } => {
+            msg!("DREGAN Access: Set Weighted Mint");
+            process_set_weighted_mint(program_id, accounts, index, mint, weight)
+        }
+        AccessInstruction::RefreshNft => {
+            msg!("DREGAN Access: Refresh NFT");
+            process_refresh_nft(program_id, accounts)
+        }
+        AccessInstruction::MigrateConfig { bump } => {
+            msg!("DREGAN Access: Migrate Config");
+            process_migrate_config(program_id, accounts, bump)
+        }
+        AccessInstruction::MigrateAccess => {
+            msg!("DREGAN Access: Migrate Access Account");
+            process_migrate_access(program_id, accounts)
+        }
     }
 }
 
@@ -143,44 +1090,120 @@ fn process_initialize_config(
     let config_account = next_account_info(accounts_iter)?;
     let authority = next_account_info(accounts_iter)?;
     let token_mint = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
     
     if !authority.is_signer {
         return Err(ProgramError::MissingRequiredSignature);
     }
     
-    // Verify config_account is owned by this program
-    if config_account.owner != program_id {
+    let (expected_config, expected_bump) = Pubkey::find_program_address(&[CONFIG_SEED], program_id);
+    if *config_account.key != expected_config || bump != expected_bump {
+        msg!("Invalid access config PDA");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    
+    // Read decimals from the mint so default thresholds are in base units
+    if token_mint.owner != &spl_token::id() {
+        msg!("Invalid token mint - not owned by token program");
         return Err(ProgramError::InvalidAccountOwner);
     }
+    let mint_data = Mint::unpack(&token_mint.data.borrow())?;
+    let config = AccessConfig::new(*authority.key, *token_mint.key, mint_data.decimals, bump)?;
     
-    let config = AccessConfig {
-        is_initialized: true,
-        authority: *authority.key,
-        token_mint: *token_mint.key,
-        bump,
-    };
+    create_pda_account(
+        program_id,
+        authority,
+        config_account,
+        system_program_account,
+        AccessConfig::LEN,
+        &[CONFIG_SEED, &[bump]],
+    )?;
     
     config.serialize(&mut &mut config_account.data.borrow_mut()[..])?;
-    msg!("Access config initialized, token mint: {}", token_mint.key);
+    msg!(
+        "Access config initialized, token mint: {} ({} decimals), thresholds: {:?}",
+        token_mint.key,
+        mint_data.decimals,
+        config.thresholds
+    );
     Ok(())
 }
 
-fn process_initialize_access(
+/// Load the access config, rejecting anything but the initialized config PDA.
+/// Authority, thresholds, bounty and treasury all come from it, so a look-alike
+/// config account must never be accepted in its place.
+fn load_config(
     program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    bump: u8,
-) -> ProgramResult {
-    let accounts_iter = &mut accounts.iter();
-    let access_account = next_account_info(accounts_iter)?;
-    let owner = next_account_info(accounts_iter)?;
+    config_account: &AccountInfo,
+) -> Result<AccessConfig, ProgramError> {
+    if config_account.owner != program_id {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
     
-    if !owner.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
+    let (expected_config, _) = Pubkey::find_program_address(&[CONFIG_SEED], program_id);
+    if *config_account.key != expected_config {
+        msg!("Invalid access config PDA");
+        return Err(ProgramError::InvalidSeeds);
     }
     
-    // Verify access_account is owned by this program
-    if access_account.owner != program_id {
-        return Err(ProgramError::InvalidAccountOwner);
+    let config = AccessConfig::try_from_slice(&config_account.data.borrow())?;
+    if !config.is_initialized {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    Ok(config)
+}
+
+/// Create `account` at a PDA of this program with `space` bytes, rent-exempt and
+/// paid for by `payer`. Fails if the program already owns it, so every account
+/// created this way is initialized exactly once. Lamports sent to the address
+/// beforehand are topped up rather than blocking creation.
+fn create_pda_account<'a>(
+    program_id: &Pubkey,
+    payer: &AccountInfo<'a>,
+    account: &AccountInfo<'a>,
+    system_program_account: &AccountInfo<'a>,
+    space: usize,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    if *system_program_account.key != system_program::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    
+    if account.owner == program_id || account.data_len() > 0 {
+        msg!("Account {} already initialized", account.key);
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+    
+    let required = Rent::get()?.minimum_balance(space);
+    let accounts = [payer.clone(), account.clone(), system_program_account.clone()];
+    if account.lamports() == 0 {
+        return invoke_signed(
+            &system_instruction::create_account(payer.key, account.key, required, space as u64, program_id),
+            &accounts,
+            &[seeds],
+        );
+    }
+    
+    let top_up = required.saturating_sub(account.lamports());
+    if top_up > 0 {
+        invoke(&system_instruction::transfer(payer.key, account.key, top_up), &accounts)?;
+    }
+    invoke_signed(&system_instruction::allocate(account.key, space as u64), &accounts, &[seeds])?;
+    invoke_signed(&system_instruction::assign(account.key, program_id), &accounts, &[seeds])
+}
+
+fn process_initialize_access(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    bump: u8,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let access_account = next_account_info(accounts_iter)?;
+    let owner = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+    
+    if !owner.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
     }
     
     // Verify PDA derivation
@@ -193,12 +1216,35 @@ fn process_initialize_access(
         return Err(ProgramError::InvalidSeeds);
     }
     
+    create_pda_account(
+        program_id,
+        owner,
+        access_account,
+        system_program_account,
+        AccessAccount::LEN,
+        &[ACCESS_SEED, owner.key.as_ref(), &[bump]],
+    )?;
+    
     let access_data = AccessAccount {
         is_initialized: true,
         owner: *owner.key,
         current_tier: AccessTier::None,
         last_verified_balance: 0,
         verification_timestamp: 0,
+        nft_mint: Pubkey::default(),
+        nft_tier: AccessTier::None,
+        nft_generation: 0,
+        pending_tier: AccessTier::None,
+        pending_slot: 0,
+        checkpoints: [BalanceCheckpoint::default(); TWAB_CHECKPOINTS],
+        checkpoint_head: 0,
+        holdings_hash: [0; 32],
+        subscription_tier: AccessTier::None,
+        subscription_expiry: 0,
+        downgrade_pending_since: 0,
+        linked_wallets: [Pubkey::default(); MAX_LINKED_WALLETS],
+        granted_tier: AccessTier::None,
+        grant_expiry: 0,
         bump,
     };
     
@@ -216,32 +1262,96 @@ fn process_verify_access(
     let owner = next_account_info(accounts_iter)?;
     let user_token_account = next_account_info(accounts_iter)?;
     let config_account = next_account_info(accounts_iter)?;
+    let sysvar_instructions = next_account_info(accounts_iter)?;
     
     if !owner.is_signer {
         return Err(ProgramError::MissingRequiredSignature);
     }
     
     // Verify accounts owned by program
-    if access_account.owner != program_id || config_account.owner != program_id {
+    if access_account.owner != program_id {
         return Err(ProgramError::InvalidAccountOwner);
     }
     
+    verify_standalone_transaction(program_id, sysvar_instructions)?;
+    
     let mut access_data = AccessAccount::try_from_slice(&access_account.data.borrow())?;
-    let config = AccessConfig::try_from_slice(&config_account.data.borrow())?;
+    let config = load_config(program_id, config_account)?;
     
     if !access_data.is_initialized {
         return Err(ProgramError::UninitializedAccount);
     }
     
-    if !config.is_initialized {
-        return Err(ProgramError::UninitializedAccount);
-    }
-    
     if access_data.owner != *owner.key {
         msg!("Access account owner mismatch");
         return Err(ProgramError::InvalidAccountOwner);
     }
     
+    let (holdings, holdings_hash) =
+        read_holdings(owner.key, user_token_account, accounts_iter.as_slice(), &config, false)?;
+    let clock = Clock::get()?;
+    access_data.holdings_hash = holdings_hash;
+    let balance = access_data.record_balance(holdings, &config, clock.unix_timestamp);
+    let balance_tier = AccessTier::from_balance(balance, &config.thresholds);
+    let new_tier = access_data.apply_verification(balance, balance_tier, &config, &clock);
+    
+    access_data.serialize(&mut &mut access_account.data.borrow_mut()[..])?;
+    
+    msg!(
+        "Access verified: balance = {}, tier = {:?} (level {})",
+        balance,
+        new_tier,
+        new_tier.to_u8()
+    );
+    Ok(())
+}
+
+/// Flash-loan guard for instructions that grant a tier from a balance read.
+/// Every top-level instruction in the transaction must belong to this program
+/// or the compute budget program: nothing else can lend DREGAN before the read
+/// and take it back after, and a lender's CPI into this program is rejected
+/// because the top-level instruction running it is then not ours.
+fn verify_standalone_transaction(
+    program_id: &Pubkey,
+    sysvar_instructions: &AccountInfo,
+) -> ProgramResult {
+    if *sysvar_instructions.key != sysvar::instructions::id() {
+        msg!("Invalid instructions sysvar");
+        return Err(ProgramError::InvalidArgument);
+    }
+    
+    let current_index = load_current_index_checked(sysvar_instructions)? as usize;
+    let current = load_instruction_at_checked(current_index, sysvar_instructions)?;
+    if current.program_id != *program_id {
+        msg!("Verification must be a top-level instruction");
+        return Err(ProgramError::Custom(3));
+    }
+    
+    let mut index = 0;
+    while let Ok(instruction) = load_instruction_at_checked(index, sysvar_instructions) {
+        if instruction.program_id != *program_id
+            && instruction.program_id != COMPUTE_BUDGET_PROGRAM_ID
+        {
+            msg!(
+                "Verification cannot share a transaction with program {}",
+                instruction.program_id
+            );
+            return Err(ProgramError::Custom(3));
+        }
+        index += 1;
+    }
+    
+    Ok(())
+}
+
+/// Read the owner's DREGAN-equivalent balance from a token account, checking
+/// that it is an SPL Token account held by `owner` of the configured mint or
+/// one of the weighted mints.
+fn read_token_balance(
+    owner: &Pubkey,
+    user_token_account: &AccountInfo,
+    config: &AccessConfig,
+) -> Result<u64, ProgramError> {
     // Verify user_token_account is an SPL Token account
     if user_token_account.owner != &spl_token::id() {
         msg!("Invalid token account - not owned by token program");
@@ -252,33 +1362,406 @@ fn process_verify_access(
     let token_data = TokenAccount::unpack(&user_token_account.data.borrow())?;
     
     // Verify token account belongs to the owner
-    if token_data.owner != *owner.key {
+    if token_data.owner != *owner {
         msg!("Token account owner mismatch");
         return Err(ProgramError::InvalidAccountOwner);
     }
     
-    // Verify token account is for the correct mint
-    if token_data.mint != config.token_mint {
-        msg!("Token mint mismatch. Expected: {}, Got: {}", config.token_mint, token_data.mint);
+    // Verify token account is for an accepted mint, weighting non-DREGAN holdings
+    match config.dregan_equivalent(&token_data.mint, token_data.amount) {
+        Some(amount) => Ok(amount),
+        None => {
+            msg!("Token mint {} is not DREGAN or an accepted weighted mint", token_data.mint);
+            Err(ProgramError::InvalidAccountData)
+        }
+    }
+}
+
+/// Sum of everything the owner holds: `user_token_account` plus any extra
+/// accounts passed after the fixed ones. Each extra is either another token
+/// account of the configured mint, or a dregan-staking position given as a
+/// `stake_account, stake_pool_config` pair. Repeated accounts count once.
+///
+/// Also returns a hash of the sorted account set, which RefreshAccess makes
+/// keepers reproduce so they cannot leave accounts out. With `lenient`, token
+/// accounts that are no longer the owner's (closed or moved) count as zero.
+fn read_holdings(
+    owner: &Pubkey,
+    user_token_account: &AccountInfo,
+    extra_accounts: &[AccountInfo],
+    config: &AccessConfig,
+    lenient: bool,
+) -> Result<(u64, [u8; 32]), ProgramError> {
+    let read_token = |account: &AccountInfo| match read_token_balance(owner, account, config) {
+        Err(_) if lenient => Ok(0),
+        result => result,
+    };
+    
+    let mut total = read_token(user_token_account)?;
+    let mut counted = vec![*user_token_account.key];
+    let mut account_set = vec![*user_token_account.key];
+    let extra_iter = &mut extra_accounts.iter();
+    
+    while let Some(account) = extra_iter.next() {
+        let amount = if account.owner == &dregan_staking::id() {
+            let stake_pool_config = next_account_info(extra_iter)?;
+            account_set.push(*stake_pool_config.key);
+            read_staked_balance(owner, account, stake_pool_config, config)?
+        } else {
+            read_token(account)?
+        };
+        account_set.push(*account.key);
+        if counted.contains(account.key) {
+            continue;
+        }
+        counted.push(*account.key);
+        total = total.checked_add(amount).ok_or(ProgramError::ArithmeticOverflow)?;
+    }
+    
+    account_set.sort();
+    account_set.dedup();
+    let seeds: Vec<&[u8]> = account_set.iter().map(|key| key.as_ref()).collect();
+    Ok((total, hashv(&seeds).to_bytes()))
+}
+
+/// Read the owner's staked amount from their dregan-staking StakeAccount,
+/// checking the PDA and that the pool it belongs to stakes the configured mint.
+fn read_staked_balance(
+    owner: &Pubkey,
+    stake_account: &AccountInfo,
+    stake_pool_config: &AccountInfo,
+    config: &AccessConfig,
+) -> Result<u64, ProgramError> {
+    if stake_account.owner != &dregan_staking::id()
+        || stake_pool_config.owner != &dregan_staking::id()
+    {
+        msg!("Stake accounts not owned by the staking program");
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    let (expected_stake, _) = Pubkey::find_program_address(
+        &[STAKE_SEED, owner.as_ref()],
+        &dregan_staking::id(),
+    );
+    if *stake_account.key != expected_stake {
+        msg!("Invalid stake account PDA");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    let (expected_pool, _) = Pubkey::find_program_address(&[STAKE_POOL_SEED], &dregan_staking::id());
+    if *stake_pool_config.key != expected_pool {
+        msg!("Invalid stake pool PDA");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    
+    let stake = StakeAccount::try_from_slice(&stake_account.data.borrow())?;
+    let pool = StakePoolConfig::try_from_slice(&stake_pool_config.data.borrow())?;
+    
+    if !stake.is_initialized || !pool.is_initialized {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    
+    if stake.owner != *owner {
+        msg!("Stake account owner mismatch");
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    if pool.token_mint != config.token_mint {
+        msg!("Stake pool mint mismatch. Expected: {}, Got: {}", config.token_mint, pool.token_mint);
         return Err(ProgramError::InvalidAccountData);
     }
     
-    let balance = token_data.amount;
-    let new_tier = AccessTier::from_balance(balance);
+    // Unbonding positions are on their way out and no longer count
+    if stake.unbonding_until != 0 {
+        return Ok(0);
+    }
+    
+    Ok(stake.amount)
+}
+
+fn process_refresh_access(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let access_account = next_account_info(accounts_iter)?;
+    let user_token_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let keeper = next_account_info(accounts_iter)?;
+    let bounty_vault = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+    
+    if !keeper.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    // Verify accounts owned by program
+    if access_account.owner != program_id {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    if *system_program_account.key != system_program::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    
+    let mut access_data = AccessAccount::try_from_slice(&access_account.data.borrow())?;
+    let config = load_config(program_id, config_account)?;
+    
+    // The vault belongs to the config whose thresholds and bounty apply
+    let (expected_vault, vault_bump) = Pubkey::find_program_address(
+        &[BOUNTY_VAULT_SEED, config_account.key.as_ref()],
+        program_id,
+    );
+    if *bounty_vault.key != expected_vault {
+        msg!("Invalid bounty vault PDA");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    
+    if !access_data.is_initialized {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    
+    // Keepers must present exactly the accounts the owner last verified with
+    let (holdings, holdings_hash) = read_holdings(
+        &access_data.owner,
+        user_token_account,
+        accounts_iter.as_slice(),
+        &config,
+        true,
+    )?;
+    if holdings_hash != access_data.holdings_hash {
+        msg!("Accounts do not match the owner's last verification");
+        return Err(ProgramError::InvalidArgument);
+    }
+    
     let clock = Clock::get()?;
+    let previous_tier = access_data.current_tier.clone();
+    let balance = access_data.record_balance(holdings, &config, clock.unix_timestamp);
+    let balance_tier = AccessTier::from_balance(balance, &config.thresholds);
     
+    // Only ever lower: the refreshed tier is capped by what is stored (or None once
+    // stale), and drops honour the same grace period and hysteresis as verification
+    let holding_tier = access_data.holding_tier(&config, clock.unix_timestamp);
+    let new_tier = access_data
+        .soften_downgrade(balance, balance_tier.clone(), &holding_tier, &config, clock.unix_timestamp)
+        .min(holding_tier);
+    if balance_tier < access_data.pending_tier {
+        access_data.pending_tier = AccessTier::None;
+        access_data.pending_slot = 0;
+    }
     access_data.current_tier = new_tier.clone();
-    access_data.last_verified_balance = balance;
-    access_data.verification_timestamp = clock.unix_timestamp;
-    
     access_data.serialize(&mut &mut access_account.data.borrow_mut()[..])?;
     
+    if new_tier >= previous_tier {
+        msg!("Access unchanged: balance = {}, tier = {:?}", balance, new_tier);
+        return Ok(());
+    }
+    
+    // Bounty is best effort: skipped when unset or the vault would drop below rent exemption
+    let rent_floor = Rent::get()?.minimum_balance(0);
+    if config.refresh_bounty > 0
+        && bounty_vault.lamports() >= rent_floor.saturating_add(config.refresh_bounty)
+    {
+        invoke_signed(
+            &system_instruction::transfer(bounty_vault.key, keeper.key, config.refresh_bounty),
+            &[
+                bounty_vault.clone(),
+                keeper.clone(),
+                system_program_account.clone(),
+            ],
+            &[&[BOUNTY_VAULT_SEED, config_account.key.as_ref(), &[vault_bump]]],
+        )?;
+        msg!("Paid refresh bounty of {} lamports to {}", config.refresh_bounty, keeper.key);
+    }
+    
     msg!(
-        "Access verified: balance = {}, tier = {:?} (level {})",
-        balance,
+        "Access refreshed for {}: {:?} -> {:?} (balance = {})",
+        access_data.owner,
+        previous_tier,
         new_tier,
-        new_tier.to_u8()
+        balance
+    );
+    Ok(())
+}
+
+/// Wallet whose access account `owner` acts on: `owner` itself, or the primary
+/// named by its wallet link when one is passed.
+fn resolve_access_holder(
+    program_id: &Pubkey,
+    owner: &AccountInfo,
+    wallet_link: Option<&AccountInfo>,
+) -> Result<Pubkey, ProgramError> {
+    let wallet_link = match wallet_link {
+        Some(wallet_link) => wallet_link,
+        None => return Ok(*owner.key),
+    };
+    
+    if wallet_link.owner != program_id {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    let (expected_link, _) = Pubkey::find_program_address(
+        &[WALLET_LINK_SEED, owner.key.as_ref()],
+        program_id,
+    );
+    if *wallet_link.key != expected_link {
+        msg!("Invalid wallet link PDA");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    
+    let link = WalletLink::try_from_slice(&wallet_link.data.borrow())?;
+    if !link.is_initialized {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    
+    Ok(link.primary)
+}
+
+fn process_link_wallet(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    bump: u8,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let access_account = next_account_info(accounts_iter)?;
+    let owner = next_account_info(accounts_iter)?;
+    let secondary = next_account_info(accounts_iter)?;
+    let wallet_link = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+    
+    // Both sides consent: the owner delegates, the secondary accepts
+    if !owner.is_signer || !secondary.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    // Verify accounts owned by program
+    if access_account.owner != program_id {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    // Verify PDA derivation
+    let (expected_link, expected_bump) = Pubkey::find_program_address(
+        &[WALLET_LINK_SEED, secondary.key.as_ref()],
+        program_id,
+    );
+    if *wallet_link.key != expected_link || bump != expected_bump {
+        msg!("Invalid wallet link PDA");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    
+    let mut access_data = AccessAccount::try_from_slice(&access_account.data.borrow())?;
+    
+    if !access_data.is_initialized {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    
+    if access_data.owner != *owner.key {
+        msg!("Access account owner mismatch");
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    if secondary.key == owner.key {
+        msg!("Cannot link the owner to itself");
+        return Err(ProgramError::InvalidArgument);
+    }
+    
+    // Created on the first link; UnlinkWallet leaves it in place for a re-link
+    if wallet_link.owner == program_id {
+        if WalletLink::try_from_slice(&wallet_link.data.borrow())?.is_initialized {
+            msg!("Wallet {} is already linked", secondary.key);
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+    } else {
+        create_pda_account(
+            program_id,
+            owner,
+            wallet_link,
+            system_program_account,
+            WalletLink::LEN,
+            &[WALLET_LINK_SEED, secondary.key.as_ref(), &[bump]],
+        )?;
+    }
+    
+    let slot = access_data
+        .linked_wallets
+        .iter_mut()
+        .find(|wallet| **wallet == Pubkey::default())
+        .ok_or_else(|| {
+            msg!("All {} linked wallet slots in use", MAX_LINKED_WALLETS);
+            ProgramError::Custom(9)
+        })?;
+    *slot = *secondary.key;
+    
+    let link = WalletLink {
+        is_initialized: true,
+        secondary: *secondary.key,
+        primary: *owner.key,
+        bump,
+    };
+    
+    link.serialize(&mut &mut wallet_link.data.borrow_mut()[..])?;
+    access_data.serialize(&mut &mut access_account.data.borrow_mut()[..])?;
+    
+    msg!("Linked wallet {} to {}", secondary.key, owner.key);
+    Ok(())
+}
+
+fn process_unlink_wallet(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let access_account = next_account_info(accounts_iter)?;
+    let signer = next_account_info(accounts_iter)?;
+    let wallet_link = next_account_info(accounts_iter)?;
+    
+    if !signer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    // Verify accounts owned by program
+    if access_account.owner != program_id || wallet_link.owner != program_id {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    let mut access_data = AccessAccount::try_from_slice(&access_account.data.borrow())?;
+    let link = WalletLink::try_from_slice(&wallet_link.data.borrow())?;
+    
+    if !access_data.is_initialized || !link.is_initialized {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    
+    let (expected_link, _) = Pubkey::find_program_address(
+        &[WALLET_LINK_SEED, link.secondary.as_ref()],
+        program_id,
     );
+    if *wallet_link.key != expected_link || link.primary != access_data.owner {
+        msg!("Wallet link does not belong to this access account");
+        return Err(ProgramError::InvalidArgument);
+    }
+    
+    if *signer.key != access_data.owner && *signer.key != link.secondary {
+        msg!("Only the owner or the linked wallet can unlink");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    for wallet in access_data.linked_wallets.iter_mut() {
+        if *wallet == link.secondary {
+            *wallet = Pubkey::default();
+        }
+    }
+    
+    // Keep the account for a later re-link; only the flag matters
+    let cleared = WalletLink {
+        is_initialized: false,
+        secondary: Pubkey::default(),
+        primary: Pubkey::default(),
+        bump: link.bump,
+    };
+    
+    cleared.serialize(&mut &mut wallet_link.data.borrow_mut()[..])?;
+    access_data.serialize(&mut &mut access_account.data.borrow_mut()[..])?;
+    
+    msg!("Unlinked wallet {} from {}", link.secondary, link.primary);
     Ok(())
 }
 
@@ -288,24 +1771,2521 @@ fn process_check_tier(
 ) -> ProgramResult {
     let accounts_iter = &mut accounts.iter();
     let access_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
     
-    // Verify account owned by program
+    // Verify accounts owned by program
     if access_account.owner != program_id {
         return Err(ProgramError::InvalidAccountOwner);
     }
     
     let access_data = AccessAccount::try_from_slice(&access_account.data.borrow())?;
+    let config = load_config(program_id, config_account)?;
     
     if !access_data.is_initialized {
         return Err(ProgramError::UninitializedAccount);
     }
     
+    if let Some(wallet_link) = accounts_iter.next() {
+        if wallet_link.owner != program_id {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+        let link = WalletLink::try_from_slice(&wallet_link.data.borrow())?;
+        let (expected_link, _) = Pubkey::find_program_address(
+            &[WALLET_LINK_SEED, link.secondary.as_ref()],
+            program_id,
+        );
+        if *wallet_link.key != expected_link
+            || !link.is_initialized
+            || link.primary != access_data.owner
+            || !access_data.linked_wallets.contains(&link.secondary)
+        {
+            msg!("Wallet link does not resolve to this access account");
+            return Err(ProgramError::InvalidArgument);
+        }
+        msg!("Secondary wallet {} resolves to {}", link.secondary, link.primary);
+    }
+    
+    let clock = Clock::get()?;
+    let effective_tier = access_data.effective_tier(&config, clock.unix_timestamp);
+    if access_data.is_stale(&config, clock.unix_timestamp) {
+        msg!(
+            "Verification stale: last verified at {}, valid for {}s. Re-verify to restore {:?}",
+            access_data.verification_timestamp,
+            config.verification_validity,
+            access_data.current_tier
+        );
+    }
+    if access_data.subscribed_tier(clock.unix_timestamp) != AccessTier::None {
+        msg!(
+            "Subscribed to {:?} until {}",
+            access_data.subscription_tier,
+            access_data.subscription_expiry
+        );
+    }
+    if access_data.complimentary_tier(clock.unix_timestamp) != AccessTier::None {
+        msg!(
+            "Granted {:?} until {}",
+            access_data.granted_tier,
+            access_data.grant_expiry
+        );
+    }
+    
     msg!(
         "Current tier: {:?} (level {}), last verified balance: {}, verified at: {}",
-        access_data.current_tier,
-        access_data.current_tier.to_u8(),
+        effective_tier,
+        effective_tier.to_u8(),
         access_data.last_verified_balance,
         access_data.verification_timestamp
     );
     Ok(())
 }
+
+fn process_require_tier(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    min_tier: AccessTier,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let access_account = next_account_info(accounts_iter)?;
+    let owner = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    
+    // Verify accounts owned by program
+    if access_account.owner != program_id {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    // Callers name the user, so the access account must be theirs, or their
+    // primary's when a wallet link is passed
+    let holder = resolve_access_holder(program_id, owner, accounts_iter.next())?;
+    let (expected_pda, _) = Pubkey::find_program_address(
+        &[ACCESS_SEED, holder.as_ref()],
+        program_id,
+    );
+    if *access_account.key != expected_pda {
+        msg!("Invalid access account PDA");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    
+    let access_data = AccessAccount::try_from_slice(&access_account.data.borrow())?;
+    let config = load_config(program_id, config_account)?;
+    
+    if !access_data.is_initialized {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    
+    if holder != *owner.key && !access_data.linked_wallets.contains(owner.key) {
+        msg!("Wallet {} is not linked to {}", owner.key, holder);
+        return Err(ProgramError::InvalidArgument);
+    }
+    
+    let clock = Clock::get()?;
+    let effective_tier = access_data.effective_tier(&config, clock.unix_timestamp);
+    
+    if effective_tier < min_tier {
+        msg!("Tier {:?} below required {:?}", effective_tier, min_tier);
+        return Err(ProgramError::Custom(5));
+    }
+    
+    set_return_data(&effective_tier.try_to_vec()?);
+    msg!("Tier {:?} meets required {:?}", effective_tier, min_tier);
+    Ok(())
+}
+
+fn process_initialize_feature_registry(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    bump: u8,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let registry_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+    
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    // Verify PDA derivation
+    let (expected_pda, expected_bump) = Pubkey::find_program_address(
+        &[FEATURE_REGISTRY_SEED, config_account.key.as_ref()],
+        program_id,
+    );
+    if *registry_account.key != expected_pda || bump != expected_bump {
+        msg!("Invalid feature registry PDA");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    
+    let config = load_config(program_id, config_account)?;
+    
+    if config.authority != *authority.key {
+        msg!("Config authority mismatch");
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    create_pda_account(
+        program_id,
+        authority,
+        registry_account,
+        system_program_account,
+        FeatureRegistry::LEN,
+        &[FEATURE_REGISTRY_SEED, config_account.key.as_ref(), &[bump]],
+    )?;
+    
+    let registry = FeatureRegistry {
+        is_initialized: true,
+        config: *config_account.key,
+        feature_tiers: FeatureRegistry::default_feature_tiers(),
+        feature_quotas: FeatureRegistry::default_feature_quotas(),
+        bump,
+    };
+    
+    registry.serialize(&mut &mut registry_account.data.borrow_mut()[..])?;
+    msg!("Feature registry initialized for config {}", config_account.key);
+    Ok(())
+}
+
+fn process_set_feature_tiers(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    feature_id: u8,
+    tier_mask: u8,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let registry_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    // Verify accounts owned by program
+    if registry_account.owner != program_id {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    let mut registry = FeatureRegistry::try_from_slice(&registry_account.data.borrow())?;
+    let config = load_config(program_id, config_account)?;
+    
+    if !registry.is_initialized {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    
+    if registry.config != *config_account.key {
+        msg!("Feature registry belongs to another config");
+        return Err(ProgramError::InvalidArgument);
+    }
+    
+    if config.authority != *authority.key {
+        msg!("Config authority mismatch");
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    if feature_id as usize >= MAX_FEATURES || tier_mask & !FeatureRegistry::ALL_TIERS != 0 {
+        msg!("Invalid feature id {} or tier mask {:#06b}", feature_id, tier_mask);
+        return Err(ProgramError::InvalidArgument);
+    }
+    
+    registry.feature_tiers[feature_id as usize] = tier_mask;
+    registry.serialize(&mut &mut registry_account.data.borrow_mut()[..])?;
+    
+    msg!("Feature {} tier mask set to {:#06b}", feature_id, tier_mask);
+    Ok(())
+}
+
+fn process_has_feature(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    feature_id: u8,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let access_account = next_account_info(accounts_iter)?;
+    let owner = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let registry_account = next_account_info(accounts_iter)?;
+    
+    // Verify accounts owned by program
+    if access_account.owner != program_id || registry_account.owner != program_id {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    // Callers name the user, so the access account must be theirs, or their
+    // primary's when a wallet link is passed
+    let holder = resolve_access_holder(program_id, owner, accounts_iter.next())?;
+    let (expected_pda, _) = Pubkey::find_program_address(
+        &[ACCESS_SEED, holder.as_ref()],
+        program_id,
+    );
+    if *access_account.key != expected_pda {
+        msg!("Invalid access account PDA");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    
+    let access_data = AccessAccount::try_from_slice(&access_account.data.borrow())?;
+    let config = load_config(program_id, config_account)?;
+    let registry = FeatureRegistry::try_from_slice(&registry_account.data.borrow())?;
+    
+    if !access_data.is_initialized || !registry.is_initialized {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    
+    if registry.config != *config_account.key {
+        msg!("Feature registry belongs to another config");
+        return Err(ProgramError::InvalidArgument);
+    }
+    
+    if holder != *owner.key && !access_data.linked_wallets.contains(owner.key) {
+        msg!("Wallet {} is not linked to {}", owner.key, holder);
+        return Err(ProgramError::InvalidArgument);
+    }
+    
+    let clock = Clock::get()?;
+    let effective_tier = access_data.effective_tier(&config, clock.unix_timestamp);
+    
+    if !registry.has_feature(feature_id, &effective_tier) {
+        msg!("Tier {:?} not entitled to feature {}", effective_tier, feature_id);
+        return Err(ProgramError::Custom(6));
+    }
+    
+    set_return_data(&effective_tier.try_to_vec()?);
+    msg!("Tier {:?} entitled to feature {}", effective_tier, feature_id);
+    Ok(())
+}
+
+fn process_set_feature_quotas(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    feature_id: u8,
+    quotas: [u32; 4],
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let registry_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    // Verify accounts owned by program
+    if registry_account.owner != program_id {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    let mut registry = FeatureRegistry::try_from_slice(&registry_account.data.borrow())?;
+    let config = load_config(program_id, config_account)?;
+    
+    if !registry.is_initialized {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    
+    if registry.config != *config_account.key {
+        msg!("Feature registry belongs to another config");
+        return Err(ProgramError::InvalidArgument);
+    }
+    
+    if config.authority != *authority.key {
+        msg!("Config authority mismatch");
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    if feature_id as usize >= MAX_FEATURES {
+        msg!("Invalid feature id {}", feature_id);
+        return Err(ProgramError::InvalidArgument);
+    }
+    
+    registry.feature_quotas[feature_id as usize] = quotas;
+    registry.serialize(&mut &mut registry_account.data.borrow_mut()[..])?;
+    
+    msg!("Feature {} quotas set to {:?}", feature_id, quotas);
+    Ok(())
+}
+
+fn process_set_platform_signer(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    signer: Pubkey,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let config_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    let mut config = load_config(program_id, config_account)?;
+    
+    if config.authority != *authority.key {
+        msg!("Config authority mismatch");
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    config.platform_signer = signer;
+    config.serialize(&mut &mut config_account.data.borrow_mut()[..])?;
+    
+    msg!("Platform signer set to {}", signer);
+    Ok(())
+}
+
+fn process_initialize_usage(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    bump: u8,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let usage_account = next_account_info(accounts_iter)?;
+    let owner = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+    
+    if !owner.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    // Verify PDA derivation
+    let (expected_pda, expected_bump) = Pubkey::find_program_address(
+        &[USAGE_SEED, owner.key.as_ref()],
+        program_id,
+    );
+    if *usage_account.key != expected_pda || bump != expected_bump {
+        msg!("Invalid usage account PDA");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    
+    create_pda_account(
+        program_id,
+        owner,
+        usage_account,
+        system_program_account,
+        UsageAccount::LEN,
+        &[USAGE_SEED, owner.key.as_ref(), &[bump]],
+    )?;
+    
+    let clock = Clock::get()?;
+    let usage = UsageAccount {
+        is_initialized: true,
+        owner: *owner.key,
+        period_start: clock.unix_timestamp,
+        used: [0; MAX_FEATURES],
+        bump,
+    };
+    
+    usage.serialize(&mut &mut usage_account.data.borrow_mut()[..])?;
+    msg!("Usage account initialized for {}", owner.key);
+    Ok(())
+}
+
+fn process_consume_quota(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    feature_id: u8,
+    units: u32,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let usage_account = next_account_info(accounts_iter)?;
+    let access_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let registry_account = next_account_info(accounts_iter)?;
+    let platform_signer = next_account_info(accounts_iter)?;
+    
+    if !platform_signer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    // Verify accounts owned by program
+    if usage_account.owner != program_id
+        || access_account.owner != program_id
+        || registry_account.owner != program_id
+    {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    let mut usage = UsageAccount::try_from_slice(&usage_account.data.borrow())?;
+    let access_data = AccessAccount::try_from_slice(&access_account.data.borrow())?;
+    let config = load_config(program_id, config_account)?;
+    let registry = FeatureRegistry::try_from_slice(&registry_account.data.borrow())?;
+    
+    if !usage.is_initialized
+        || !access_data.is_initialized
+        || !registry.is_initialized
+    {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    
+    if config.platform_signer == Pubkey::default() || config.platform_signer != *platform_signer.key {
+        msg!("Platform signer mismatch");
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    if registry.config != *config_account.key {
+        msg!("Feature registry belongs to another config");
+        return Err(ProgramError::InvalidArgument);
+    }
+    
+    // Usage and access accounts must be the same user's PDAs
+    let (expected_access, _) = Pubkey::find_program_address(
+        &[ACCESS_SEED, usage.owner.as_ref()],
+        program_id,
+    );
+    if *access_account.key != expected_access || access_data.owner != usage.owner {
+        msg!("Access account does not belong to usage owner");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    
+    let clock = Clock::get()?;
+    let effective_tier = access_data.effective_tier(&config, clock.unix_timestamp);
+    
+    if !registry.has_feature(feature_id, &effective_tier) {
+        msg!("Tier {:?} not entitled to feature {}", effective_tier, feature_id);
+        return Err(ProgramError::Custom(6));
+    }
+    
+    usage.roll_period(clock.unix_timestamp);
+    let used = usage.used[feature_id as usize]
+        .checked_add(units)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    let quota = registry.quota(feature_id, &effective_tier);
+    if quota != 0 && used > quota {
+        msg!(
+            "Quota exceeded for feature {}: {} used of {} this period",
+            feature_id,
+            usage.used[feature_id as usize],
+            quota
+        );
+        return Err(ProgramError::Custom(7));
+    }
+    usage.used[feature_id as usize] = used;
+    usage.serialize(&mut &mut usage_account.data.borrow_mut()[..])?;
+    
+    let remaining = if quota == 0 { u32::MAX } else { quota - used };
+    set_return_data(&remaining.to_le_bytes());
+    msg!(
+        "Consumed {} of feature {} for {}: {} used, {} remaining, period started {}",
+        units,
+        feature_id,
+        usage.owner,
+        used,
+        remaining,
+        usage.period_start
+    );
+    Ok(())
+}
+
+/// GrantTier with `Some((tier, expires_at))`, RevokeGrant with `None`
+fn process_grant_tier(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    user: Pubkey,
+    grant: Option<(AccessTier, i64)>,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let access_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    // Verify accounts owned by program
+    if access_account.owner != program_id {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    let (expected_pda, _) = Pubkey::find_program_address(
+        &[ACCESS_SEED, user.as_ref()],
+        program_id,
+    );
+    if *access_account.key != expected_pda {
+        msg!("Invalid access account PDA");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    
+    let mut access_data = AccessAccount::try_from_slice(&access_account.data.borrow())?;
+    let config = load_config(program_id, config_account)?;
+    
+    if !access_data.is_initialized {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    
+    if config.authority != *authority.key {
+        msg!("Config authority mismatch");
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    match grant {
+        Some((tier, expires_at)) => {
+            let clock = Clock::get()?;
+            if tier == AccessTier::None || expires_at <= clock.unix_timestamp {
+                msg!("Grant needs a tier and a future expiry");
+                return Err(ProgramError::InvalidArgument);
+            }
+            
+            access_data.granted_tier = tier.clone();
+            access_data.grant_expiry = expires_at;
+            access_data.serialize(&mut &mut access_account.data.borrow_mut()[..])?;
+            
+            AccessEvent::TierGranted {
+                authority: *authority.key,
+                user,
+                tier: tier.clone(),
+                expires_at,
+            }
+            .emit()?;
+            
+            msg!("Granted {:?} to {} until {}", tier, user, expires_at);
+        }
+        None => {
+            let tier = access_data.granted_tier.clone();
+            if tier == AccessTier::None {
+                msg!("No grant to revoke for {}", user);
+                return Err(ProgramError::InvalidArgument);
+            }
+            
+            access_data.granted_tier = AccessTier::None;
+            access_data.grant_expiry = 0;
+            access_data.serialize(&mut &mut access_account.data.borrow_mut()[..])?;
+            
+            AccessEvent::GrantRevoked {
+                authority: *authority.key,
+                user,
+                tier: tier.clone(),
+            }
+            .emit()?;
+            
+            msg!("Revoked {:?} grant from {}", tier, user);
+        }
+    }
+    Ok(())
+}
+
+fn process_set_downgrade_policy(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    grace_period: i64,
+    hysteresis_bps: u16,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let config_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    let mut config = load_config(program_id, config_account)?;
+    
+    if config.authority != *authority.key {
+        msg!("Config authority mismatch");
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    if grace_period < 0 || hysteresis_bps > MAX_HYSTERESIS_BPS {
+        msg!("Grace period must be >= 0 and hysteresis at most {} bps", MAX_HYSTERESIS_BPS);
+        return Err(ProgramError::InvalidArgument);
+    }
+    
+    config.downgrade_grace_period = grace_period;
+    config.hysteresis_bps = hysteresis_bps;
+    config.serialize(&mut &mut config_account.data.borrow_mut()[..])?;
+    
+    msg!(
+        "Downgrade policy set: grace period {}s, hysteresis {} bps",
+        grace_period,
+        hysteresis_bps
+    );
+    Ok(())
+}
+
+fn process_set_weighted_mint(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    index: u8,
+    mint: Pubkey,
+    weight: u64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let config_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    let mut config = load_config(program_id, config_account)?;
+    
+    if config.authority != *authority.key {
+        msg!("Config authority mismatch");
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    if index as usize >= MAX_WEIGHTED_MINTS {
+        msg!("Weighted mint index must be below {}", MAX_WEIGHTED_MINTS);
+        return Err(ProgramError::InvalidArgument);
+    }
+    
+    let weighted = if weight == 0 {
+        WeightedMint::default()
+    } else {
+        // Each mint is counted once, and DREGAN itself always at face value
+        let duplicate = config
+            .weighted_mints
+            .iter()
+            .enumerate()
+            .any(|(slot, weighted)| slot != index as usize && weighted.mint == mint);
+        if mint == Pubkey::default() || mint == config.token_mint || duplicate {
+            msg!("Mint {} cannot be added as a weighted mint", mint);
+            return Err(ProgramError::InvalidArgument);
+        }
+        WeightedMint { mint, weight }
+    };
+    
+    config.weighted_mints[index as usize] = weighted;
+    config.serialize(&mut &mut config_account.data.borrow_mut()[..])?;
+    
+    msg!(
+        "Weighted mint slot {} set to {} at {} per {} base units",
+        index,
+        weighted.mint,
+        weighted.weight,
+        WEIGHT_SCALE
+    );
+    Ok(())
+}
+
+fn process_set_subscription_terms(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    mint: Pubkey,
+    treasury: Pubkey,
+    prices: [u64; 3],
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let config_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    let mut config = load_config(program_id, config_account)?;
+    
+    if config.authority != *authority.key {
+        msg!("Config authority mismatch");
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    config.subscription_mint = mint;
+    config.subscription_treasury = treasury;
+    config.subscription_prices = prices;
+    config.serialize(&mut &mut config_account.data.borrow_mut()[..])?;
+    
+    msg!(
+        "Subscription terms set: mint {}, treasury {}, prices {:?}",
+        mint,
+        if treasury == Pubkey::default() { "none (burn)".to_string() } else { treasury.to_string() },
+        prices
+    );
+    Ok(())
+}
+
+fn process_subscribe(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    tier: AccessTier,
+    periods: u32,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let access_account = next_account_info(accounts_iter)?;
+    let owner = next_account_info(accounts_iter)?;
+    let payer_token_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let treasury_or_mint = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+    
+    if !owner.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    // Verify accounts owned by program
+    if access_account.owner != program_id {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    if *token_program.key != spl_token::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    
+    let mut access_data = AccessAccount::try_from_slice(&access_account.data.borrow())?;
+    let config = load_config(program_id, config_account)?;
+    
+    if !access_data.is_initialized {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    
+    if access_data.owner != *owner.key {
+        msg!("Access account owner mismatch");
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    let price = match config.subscription_price(&tier) {
+        Some(price) if periods > 0 => price,
+        _ => {
+            msg!("No subscription offered for {:?} x {} periods", tier, periods);
+            return Err(ProgramError::InvalidArgument);
+        }
+    };
+    let cost = price
+        .checked_mul(periods as u64)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    
+    // A running subscription can only be extended at the same tier
+    let clock = Clock::get()?;
+    let active_tier = access_data.subscribed_tier(clock.unix_timestamp);
+    if active_tier != AccessTier::None && active_tier != tier {
+        msg!(
+            "Subscription for {:?} active until {}",
+            active_tier,
+            access_data.subscription_expiry
+        );
+        return Err(ProgramError::Custom(8));
+    }
+    
+    if config.subscription_treasury == Pubkey::default() {
+        if *treasury_or_mint.key != config.subscription_mint {
+            msg!("Invalid subscription mint");
+            return Err(ProgramError::InvalidArgument);
+        }
+        invoke(
+            &spl_token::instruction::burn(
+                token_program.key,
+                payer_token_account.key,
+                treasury_or_mint.key,
+                owner.key,
+                &[],
+                cost,
+            )?,
+            &[
+                payer_token_account.clone(),
+                treasury_or_mint.clone(),
+                owner.clone(),
+                token_program.clone(),
+            ],
+        )?;
+    } else {
+        if *treasury_or_mint.key != config.subscription_treasury {
+            msg!("Invalid subscription treasury");
+            return Err(ProgramError::InvalidArgument);
+        }
+        // The token program checks the payer account shares the treasury's mint
+        if TokenAccount::unpack(&treasury_or_mint.data.borrow())?.mint != config.subscription_mint {
+            msg!("Treasury is not a {} account", config.subscription_mint);
+            return Err(ProgramError::InvalidAccountData);
+        }
+        invoke(
+            &spl_token::instruction::transfer(
+                token_program.key,
+                payer_token_account.key,
+                treasury_or_mint.key,
+                owner.key,
+                &[],
+                cost,
+            )?,
+            &[
+                payer_token_account.clone(),
+                treasury_or_mint.clone(),
+                owner.clone(),
+                token_program.clone(),
+            ],
+        )?;
+    }
+    
+    let duration = SUBSCRIPTION_PERIOD
+        .checked_mul(periods as i64)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    let starts_at = access_data.subscription_expiry.max(clock.unix_timestamp);
+    access_data.subscription_tier = tier.clone();
+    access_data.subscription_expiry = starts_at
+        .checked_add(duration)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    access_data.serialize(&mut &mut access_account.data.borrow_mut()[..])?;
+    
+    msg!(
+        "Subscribed {} to {:?} for {} period(s) at {} each, expires {}",
+        owner.key,
+        tier,
+        periods,
+        price,
+        access_data.subscription_expiry
+    );
+    Ok(())
+}
+
+fn process_mint_access_nft(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let access_account = next_account_info(accounts_iter)?;
+    let owner = next_account_info(accounts_iter)?;
+    let user_token_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let nft_mint = next_account_info(accounts_iter)?;
+    let owner_nft_token_account = next_account_info(accounts_iter)?;
+    let mint_authority = next_account_info(accounts_iter)?;
+    let metadata = next_account_info(accounts_iter)?;
+    let master_edition = next_account_info(accounts_iter)?;
+    let collection_mint = next_account_info(accounts_iter)?;
+    let collection_metadata = next_account_info(accounts_iter)?;
+    let collection_master_edition = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+    let associated_token_program = next_account_info(accounts_iter)?;
+    let token_metadata_program = next_account_info(accounts_iter)?;
+    let sysvar_instructions = next_account_info(accounts_iter)?;
+    
+    if !owner.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    // Verify accounts owned by program
+    if access_account.owner != program_id {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    verify_standalone_transaction(program_id, sysvar_instructions)?;
+    
+    let mut access_data = AccessAccount::try_from_slice(&access_account.data.borrow())?;
+    let config = load_config(program_id, config_account)?;
+    
+    if !access_data.is_initialized {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    
+    verify_nft_programs(
+        system_program_account,
+        token_program,
+        &config.nft_token_program(),
+        associated_token_program,
+        token_metadata_program,
+    )?;
+    
+    if access_data.owner != *owner.key {
+        msg!("Access account owner mismatch");
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    if access_data.nft_mint != Pubkey::default() {
+        msg!("Access NFT already minted: {}", access_data.nft_mint);
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+    
+    // Soulbound NFTs carry Token-2022 metadata and skip the Metaplex collection
+    if !config.soulbound {
+        if config.collection_mint == Pubkey::default() {
+            msg!("Access collection not created yet");
+            return Err(ProgramError::UninitializedAccount);
+        }
+        if *collection_mint.key != config.collection_mint {
+            msg!("Invalid collection mint");
+            return Err(ProgramError::InvalidArgument);
+        }
+    }
+    
+    // Same on-chain balance check as VerifyAccess
+    let (holdings, holdings_hash) =
+        read_holdings(owner.key, user_token_account, accounts_iter.as_slice(), &config, false)?;
+    let clock = Clock::get()?;
+    access_data.holdings_hash = holdings_hash;
+    let balance = access_data.record_balance(holdings, &config, clock.unix_timestamp);
+    let balance_tier = AccessTier::from_balance(balance, &config.thresholds);
+    let tier = access_data.apply_verification(balance, balance_tier.clone(), &config, &clock);
+    let tier_metadata = match config.metadata_for(&tier) {
+        Some(tier_metadata) => tier_metadata,
+        None if balance_tier != AccessTier::None => {
+            // A pending upgrade is rolled back with this error; confirm it via VerifyAccess first
+            msg!("Tier {:?} not confirmed yet; verify again after the slot gap", balance_tier);
+            return Err(ProgramError::Custom(4));
+        }
+        None => {
+            msg!("Balance {} below minimum tier threshold", balance);
+            return Err(ProgramError::Custom(1));
+        }
+    };
+    
+    // Verify PDA derivations; each burned NFT moves the owner to a fresh mint PDA
+    let generation = access_data.nft_generation.to_le_bytes();
+    let (expected_mint, mint_bump) = Pubkey::find_program_address(
+        &[NFT_MINT_SEED, owner.key.as_ref(), &generation],
+        program_id,
+    );
+    if *nft_mint.key != expected_mint {
+        msg!("Invalid NFT mint PDA");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    
+    let authority_bump = mint_authority_bump(program_id, mint_authority)?;
+    
+    let mint_seeds: &[&[u8]] = &[NFT_MINT_SEED, owner.key.as_ref(), &generation, &[mint_bump]];
+    mint_single_token(
+        owner,
+        owner,
+        nft_mint,
+        owner_nft_token_account,
+        mint_authority,
+        authority_bump,
+        system_program_account,
+        token_program,
+        associated_token_program,
+        mint_seeds,
+        if config.soulbound { Some(tier_metadata) } else { None },
+    )?;
+    
+    if !config.soulbound {
+        mint_metaplex_access_nft(
+            &config,
+            tier_metadata,
+            owner,
+            nft_mint,
+            mint_authority,
+            authority_bump,
+            metadata,
+            master_edition,
+            collection_mint,
+            collection_metadata,
+            collection_master_edition,
+            system_program_account,
+            token_program,
+            token_metadata_program,
+            sysvar_instructions,
+        )?;
+    }
+    
+    access_data.nft_mint = *nft_mint.key;
+    access_data.nft_tier = tier.clone();
+    
+    access_data.serialize(&mut &mut access_account.data.borrow_mut()[..])?;
+    
+    msg!(
+        "Access NFT {} minted to {}, tier = {:?} (level {}){}",
+        nft_mint.key,
+        owner.key,
+        tier,
+        tier.to_u8(),
+        if config.soulbound { ", soulbound" } else { "" }
+    );
+    Ok(())
+}
+
+/// Metaplex metadata, master edition and collection verification for a
+/// freshly minted legacy SPL Token access NFT.
+#[allow(clippy::too_many_arguments)]
+fn mint_metaplex_access_nft<'a>(
+    config: &AccessConfig,
+    tier_metadata: &TierMetadata,
+    owner: &AccountInfo<'a>,
+    nft_mint: &AccountInfo<'a>,
+    mint_authority: &AccountInfo<'a>,
+    authority_bump: u8,
+    metadata: &AccountInfo<'a>,
+    master_edition: &AccountInfo<'a>,
+    collection_mint: &AccountInfo<'a>,
+    collection_metadata: &AccountInfo<'a>,
+    collection_master_edition: &AccountInfo<'a>,
+    system_program_account: &AccountInfo<'a>,
+    token_program: &AccountInfo<'a>,
+    token_metadata_program: &AccountInfo<'a>,
+    sysvar_instructions: &AccountInfo<'a>,
+) -> ProgramResult {
+    create_metadata_and_master_edition(
+        owner,
+        nft_mint,
+        mint_authority,
+        authority_bump,
+        metadata,
+        master_edition,
+        system_program_account,
+        token_program,
+        token_metadata_program,
+        config.nft_data(tier_metadata, mint_authority.key, false),
+        None,
+    )?;
+    
+    // Verify the NFT into the DREGAN Access collection
+    let authority_seeds: &[&[u8]] = &[MINT_AUTHORITY_SEED, &[authority_bump]];
+    VerifyCollectionV1CpiBuilder::new(token_metadata_program)
+        .authority(mint_authority)
+        .metadata(metadata)
+        .collection_mint(collection_mint)
+        .collection_metadata(Some(collection_metadata))
+        .collection_master_edition(Some(collection_master_edition))
+        .system_program(system_program_account)
+        .sysvar_instructions(sysvar_instructions)
+        .invoke_signed(&[authority_seeds])
+}
+
+fn process_sync_nft(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let access_account = next_account_info(accounts_iter)?;
+    let owner = next_account_info(accounts_iter)?;
+    let user_token_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let nft_mint = next_account_info(accounts_iter)?;
+    let owner_nft_token_account = next_account_info(accounts_iter)?;
+    let mint_authority = next_account_info(accounts_iter)?;
+    let metadata = next_account_info(accounts_iter)?;
+    let master_edition = next_account_info(accounts_iter)?;
+    let collection_metadata = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+    let token_metadata_program = next_account_info(accounts_iter)?;
+    let sysvar_instructions = next_account_info(accounts_iter)?;
+    
+    if !owner.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    // Verify accounts owned by program
+    if access_account.owner != program_id {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    verify_standalone_transaction(program_id, sysvar_instructions)?;
+    
+    // Soulbound NFTs live under Token-2022 with their metadata in the mint
+    let soulbound = *nft_mint.owner == spl_token_2022::id();
+    if *system_program_account.key != system_program::id()
+        || token_program.key != nft_mint.owner
+        || (!soulbound && *token_program.key != spl_token::id())
+        || *token_metadata_program.key != mpl_token_metadata::ID
+    {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    
+    let mut access_data = AccessAccount::try_from_slice(&access_account.data.borrow())?;
+    let config = load_config(program_id, config_account)?;
+    
+    if !access_data.is_initialized {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    
+    if access_data.owner != *owner.key {
+        msg!("Access account owner mismatch");
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    if access_data.nft_mint == Pubkey::default() {
+        msg!("No access NFT minted");
+        return Err(ProgramError::Custom(2));
+    }
+    if *nft_mint.key != access_data.nft_mint {
+        msg!("NFT mint does not match access account");
+        return Err(ProgramError::InvalidArgument);
+    }
+    
+    if !soulbound
+        && (*metadata.key != Metadata::find_pda(nft_mint.key).0
+            || *master_edition.key != MasterEdition::find_pda(nft_mint.key).0
+            || *collection_metadata.key != Metadata::find_pda(&config.collection_mint).0)
+    {
+        msg!("Invalid metadata accounts");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    
+    let authority_bump = mint_authority_bump(program_id, mint_authority)?;
+    
+    // Same on-chain balance check as VerifyAccess
+    let (holdings, holdings_hash) =
+        read_holdings(owner.key, user_token_account, accounts_iter.as_slice(), &config, false)?;
+    let clock = Clock::get()?;
+    access_data.holdings_hash = holdings_hash;
+    let balance = access_data.record_balance(holdings, &config, clock.unix_timestamp);
+    let balance_tier = AccessTier::from_balance(balance, &config.thresholds);
+    let new_tier = access_data.apply_verification(balance, balance_tier.clone(), &config, &clock);
+    if new_tier == AccessTier::None && balance_tier != AccessTier::None {
+        // Stale holder with a pending rise: keep the NFT rather than burning it
+        msg!("Tier {:?} not confirmed yet; verify again after the slot gap", balance_tier);
+        return Err(ProgramError::Custom(4));
+    }
+    
+    match config.metadata_for(&new_tier) {
+        None if soulbound => {
+            // Lost access entirely: burn the token and close the owner's token account
+            invoke(
+                &spl_token_2022::instruction::burn(
+                    token_program.key,
+                    owner_nft_token_account.key,
+                    nft_mint.key,
+                    owner.key,
+                    &[],
+                    1,
+                )?,
+                &[
+                    owner_nft_token_account.clone(),
+                    nft_mint.clone(),
+                    owner.clone(),
+                    token_program.clone(),
+                ],
+            )?;
+            invoke(
+                &spl_token_2022::instruction::close_account(
+                    token_program.key,
+                    owner_nft_token_account.key,
+                    owner.key,
+                    owner.key,
+                    &[],
+                )?,
+                &[
+                    owner_nft_token_account.clone(),
+                    owner.clone(),
+                    token_program.clone(),
+                ],
+            )?;
+            
+            msg!("Soulbound access NFT {} burned", nft_mint.key);
+            access_data.nft_mint = Pubkey::default();
+            access_data.nft_tier = AccessTier::None;
+            access_data.nft_generation = access_data.nft_generation
+                .checked_add(1)
+                .ok_or(ProgramError::ArithmeticOverflow)?;
+        }
+        None => {
+            // Lost access entirely: burn the NFT (closes token, metadata and edition accounts)
+            BurnV1CpiBuilder::new(token_metadata_program)
+                .authority(owner)
+                .collection_metadata(Some(collection_metadata))
+                .metadata(metadata)
+                .edition(Some(master_edition))
+                .mint(nft_mint)
+                .token(owner_nft_token_account)
+                .system_program(system_program_account)
+                .sysvar_instructions(sysvar_instructions)
+                .spl_token_program(token_program)
+                .amount(1)
+                .invoke()?;
+            
+            msg!("Access NFT {} burned", nft_mint.key);
+            access_data.nft_mint = Pubkey::default();
+            access_data.nft_tier = AccessTier::None;
+            access_data.nft_generation = access_data.nft_generation
+                .checked_add(1)
+                .ok_or(ProgramError::ArithmeticOverflow)?;
+        }
+        Some(tier_metadata) if new_tier != access_data.nft_tier => {
+            update_nft_metadata(
+                &config,
+                tier_metadata,
+                owner,
+                nft_mint,
+                mint_authority,
+                authority_bump,
+                metadata,
+                system_program_account,
+                token_program,
+                token_metadata_program,
+            )?;
+            
+            msg!(
+                "Access NFT {} moved from {:?} to {:?}",
+                nft_mint.key,
+                access_data.nft_tier,
+                new_tier
+            );
+            access_data.nft_tier = new_tier.clone();
+        }
+        Some(_) => {
+            msg!("Access NFT already at tier {:?}", new_tier);
+        }
+    }
+    
+    access_data.serialize(&mut &mut access_account.data.borrow_mut()[..])?;
+    
+    msg!(
+        "Access synced: balance = {}, tier = {:?} (level {})",
+        balance,
+        new_tier,
+        new_tier.to_u8()
+    );
+    Ok(())
+}
+
+fn process_refresh_nft(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let access_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let keeper = next_account_info(accounts_iter)?;
+    let nft_mint = next_account_info(accounts_iter)?;
+    let mint_authority = next_account_info(accounts_iter)?;
+    let metadata = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+    let token_metadata_program = next_account_info(accounts_iter)?;
+    
+    if !keeper.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    // Verify accounts owned by program
+    if access_account.owner != program_id {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    let soulbound = *nft_mint.owner == spl_token_2022::id();
+    if *system_program_account.key != system_program::id()
+        || token_program.key != nft_mint.owner
+        || (!soulbound && *token_program.key != spl_token::id())
+        || *token_metadata_program.key != mpl_token_metadata::ID
+    {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    
+    let mut access_data = AccessAccount::try_from_slice(&access_account.data.borrow())?;
+    let config = load_config(program_id, config_account)?;
+    
+    if !access_data.is_initialized {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    
+    if access_data.nft_mint == Pubkey::default() {
+        msg!("No access NFT minted");
+        return Err(ProgramError::Custom(2));
+    }
+    if *nft_mint.key != access_data.nft_mint {
+        msg!("NFT mint does not match access account");
+        return Err(ProgramError::InvalidArgument);
+    }
+    
+    if !soulbound && *metadata.key != Metadata::find_pda(nft_mint.key).0 {
+        msg!("Invalid metadata account");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    
+    let authority_bump = mint_authority_bump(program_id, mint_authority)?;
+    
+    // Keepers only ever lower the NFT, to the tier already stored on the access account
+    let clock = Clock::get()?;
+    let tier = access_data.holding_tier(&config, clock.unix_timestamp);
+    if tier >= access_data.nft_tier {
+        msg!("Access NFT at {:?}, holding tier {:?}: nothing to lower", access_data.nft_tier, tier);
+        return Ok(());
+    }
+    
+    let revoked = TierMetadata::revoked();
+    update_nft_metadata(
+        &config,
+        config.metadata_for(&tier).unwrap_or(&revoked),
+        keeper,
+        nft_mint,
+        mint_authority,
+        authority_bump,
+        metadata,
+        system_program_account,
+        token_program,
+        token_metadata_program,
+    )?;
+    
+    msg!(
+        "Access NFT {} lowered from {:?} to {:?} by {}",
+        nft_mint.key,
+        access_data.nft_tier,
+        tier,
+        keeper.key
+    );
+    access_data.nft_tier = tier;
+    access_data.serialize(&mut &mut access_account.data.borrow_mut()[..])?;
+    Ok(())
+}
+
+fn process_create_collection(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let config_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let collection_mint = next_account_info(accounts_iter)?;
+    let authority_collection_token_account = next_account_info(accounts_iter)?;
+    let mint_authority = next_account_info(accounts_iter)?;
+    let collection_metadata = next_account_info(accounts_iter)?;
+    let collection_master_edition = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+    let associated_token_program = next_account_info(accounts_iter)?;
+    let token_metadata_program = next_account_info(accounts_iter)?;
+    
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    verify_nft_programs(
+        system_program_account,
+        token_program,
+        &spl_token::id(),
+        associated_token_program,
+        token_metadata_program,
+    )?;
+    
+    let mut config = load_config(program_id, config_account)?;
+    
+    if config.authority != *authority.key {
+        msg!("Config authority mismatch");
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    if config.collection_mint != Pubkey::default() {
+        msg!("Collection already created: {}", config.collection_mint);
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+    
+    let (expected_collection, collection_bump) = Pubkey::find_program_address(
+        &[COLLECTION_SEED],
+        program_id,
+    );
+    if *collection_mint.key != expected_collection {
+        msg!("Invalid collection mint PDA");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    
+    let authority_bump = mint_authority_bump(program_id, mint_authority)?;
+    
+    // The collection NFT itself is held by the config authority
+    let collection_seeds: &[&[u8]] = &[COLLECTION_SEED, &[collection_bump]];
+    mint_single_token(
+        authority,
+        authority,
+        collection_mint,
+        authority_collection_token_account,
+        mint_authority,
+        authority_bump,
+        system_program_account,
+        token_program,
+        associated_token_program,
+        collection_seeds,
+        None,
+    )?;
+    
+    create_metadata_and_master_edition(
+        authority,
+        collection_mint,
+        mint_authority,
+        authority_bump,
+        collection_metadata,
+        collection_master_edition,
+        system_program_account,
+        token_program,
+        token_metadata_program,
+        DataV2 {
+            name: COLLECTION_NAME.to_string(),
+            symbol: COLLECTION_SYMBOL.to_string(),
+            uri: COLLECTION_URI.to_string(),
+            seller_fee_basis_points: 0,
+            creators: Some(vec![Creator {
+                address: *mint_authority.key,
+                verified: true,
+                share: 100,
+            }]),
+            collection: None,
+            uses: None,
+        },
+        Some(CollectionDetails::V1 { size: 0 }),
+    )?;
+    
+    config.collection_mint = *collection_mint.key;
+    config.serialize(&mut &mut config_account.data.borrow_mut()[..])?;
+    
+    msg!("DREGAN Access collection created: {}", collection_mint.key);
+    Ok(())
+}
+
+fn process_set_tier_metadata(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    tier: AccessTier,
+    name: String,
+    symbol: String,
+    uri: String,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let config_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    let mut config = load_config(program_id, config_account)?;
+    
+    if config.authority != *authority.key {
+        msg!("Config authority mismatch");
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    let index = match tier {
+        AccessTier::None => {
+            msg!("Tier None has no NFT metadata");
+            return Err(ProgramError::InvalidArgument);
+        }
+        AccessTier::Basic => 0,
+        AccessTier::Pro => 1,
+        AccessTier::Elite => 2,
+    };
+    
+    // Already-minted NFTs keep their metadata until their next sync
+    config.tier_metadata[index] = TierMetadata::new(&name, &symbol, &uri)?;
+    config.serialize(&mut &mut config_account.data.borrow_mut()[..])?;
+    
+    msg!("Tier {:?} metadata set: {} ({}) {}", tier, name, symbol, uri);
+    Ok(())
+}
+
+fn process_set_soulbound(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    enabled: bool,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let config_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    let mut config = load_config(program_id, config_account)?;
+    
+    if config.authority != *authority.key {
+        msg!("Config authority mismatch");
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    // Only affects future mints; existing NFTs keep the program they were minted under
+    config.soulbound = enabled;
+    config.serialize(&mut &mut config_account.data.borrow_mut()[..])?;
+    
+    msg!("Soulbound access NFTs {}", if enabled { "enabled" } else { "disabled" });
+    Ok(())
+}
+
+fn process_update_thresholds(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    thresholds: [u64; 3],
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let config_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    let mut config = load_config(program_id, config_account)?;
+    
+    if config.authority != *authority.key {
+        msg!("Config authority mismatch");
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    AccessConfig::validate_thresholds(&thresholds)?;
+    
+    // Stored tiers only change on the holder's next verification
+    config.thresholds = thresholds;
+    config.serialize(&mut &mut config_account.data.borrow_mut()[..])?;
+    
+    msg!(
+        "Thresholds updated: {:?} ({} decimals)",
+        thresholds,
+        config.token_decimals
+    );
+    Ok(())
+}
+
+fn process_set_verification_validity(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    seconds: i64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let config_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    let mut config = load_config(program_id, config_account)?;
+    
+    if config.authority != *authority.key {
+        msg!("Config authority mismatch");
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    if seconds <= 0 {
+        msg!("Verification validity must be positive");
+        return Err(ProgramError::InvalidArgument);
+    }
+    
+    config.verification_validity = seconds;
+    config.serialize(&mut &mut config_account.data.borrow_mut()[..])?;
+    
+    msg!("Verification validity set to {}s", seconds);
+    Ok(())
+}
+
+fn process_set_verification_slot_gap(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    slots: u64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let config_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    let mut config = load_config(program_id, config_account)?;
+    
+    if config.authority != *authority.key {
+        msg!("Config authority mismatch");
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    config.verification_slot_gap = slots;
+    config.serialize(&mut &mut config_account.data.borrow_mut()[..])?;
+    
+    msg!("Verification slot gap set to {} slots", slots);
+    Ok(())
+}
+
+fn process_set_twab_window(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    seconds: i64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let config_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    let mut config = load_config(program_id, config_account)?;
+    
+    if config.authority != *authority.key {
+        msg!("Config authority mismatch");
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    if seconds < 0 {
+        msg!("TWAB window cannot be negative");
+        return Err(ProgramError::InvalidArgument);
+    }
+    
+    config.twab_window = seconds;
+    config.serialize(&mut &mut config_account.data.borrow_mut()[..])?;
+    
+    msg!("TWAB window set to {}s", seconds);
+    Ok(())
+}
+
+fn process_set_refresh_bounty(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    lamports: u64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let config_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    let mut config = load_config(program_id, config_account)?;
+    
+    if config.authority != *authority.key {
+        msg!("Config authority mismatch");
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    config.refresh_bounty = lamports;
+    config.serialize(&mut &mut config_account.data.borrow_mut()[..])?;
+    
+    msg!("Refresh bounty set to {} lamports", lamports);
+    Ok(())
+}
+
+fn verify_nft_programs(
+    system_program_account: &AccountInfo,
+    token_program: &AccountInfo,
+    token_program_id: &Pubkey,
+    associated_token_program: &AccountInfo,
+    token_metadata_program: &AccountInfo,
+) -> ProgramResult {
+    if *system_program_account.key != system_program::id()
+        || token_program.key != token_program_id
+        || *associated_token_program.key != spl_associated_token_account::id()
+        || *token_metadata_program.key != mpl_token_metadata::ID
+    {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
+
+/// Check the mint authority PDA (also the Metaplex update authority) and return its bump
+fn mint_authority_bump(
+    program_id: &Pubkey,
+    mint_authority: &AccountInfo,
+) -> Result<u8, ProgramError> {
+    let (expected_authority, authority_bump) = Pubkey::find_program_address(
+        &[MINT_AUTHORITY_SEED],
+        program_id,
+    );
+    if *mint_authority.key != expected_authority {
+        msg!("Invalid mint authority");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(authority_bump)
+}
+
+/// Create a 0-decimal mint at a program PDA, open the holder's associated token
+/// account and mint exactly one token into it. With `soulbound_metadata` the
+/// mint is a Token-2022 NonTransferable mint carrying its own metadata, and its
+/// mint authority is dropped afterwards to fix supply at 1.
+#[allow(clippy::too_many_arguments)]
+fn mint_single_token<'a>(
+    payer: &AccountInfo<'a>,
+    holder: &AccountInfo<'a>,
+    mint: &AccountInfo<'a>,
+    holder_token_account: &AccountInfo<'a>,
+    mint_authority: &AccountInfo<'a>,
+    authority_bump: u8,
+    system_program_account: &AccountInfo<'a>,
+    token_program: &AccountInfo<'a>,
+    associated_token_program: &AccountInfo<'a>,
+    mint_seeds: &[&[u8]],
+    soulbound_metadata: Option<&TierMetadata>,
+) -> ProgramResult {
+    let authority_seeds: &[&[u8]] = &[MINT_AUTHORITY_SEED, &[authority_bump]];
+    
+    // Create the mint account at its PDA
+    let rent = Rent::get()?;
+    let (space, lamports) = match soulbound_metadata {
+        Some(tier_metadata) => {
+            let space = ExtensionType::try_calculate_account_len::<Mint2022>(&[
+                ExtensionType::NonTransferable,
+                ExtensionType::MetadataPointer,
+            ])?;
+            // Token metadata is reallocated into the mint later, so fund it up front
+            let metadata_len = token_2022_metadata(tier_metadata, mint.key, mint_authority.key)
+                .tlv_size_of()?;
+            (space, rent.minimum_balance(space + metadata_len))
+        }
+        None => (Mint::LEN, rent.minimum_balance(Mint::LEN)),
+    };
+    invoke_signed(
+        &system_instruction::create_account(
+            payer.key,
+            mint.key,
+            lamports,
+            space as u64,
+            token_program.key,
+        ),
+        &[payer.clone(), mint.clone(), system_program_account.clone()],
+        &[mint_seeds],
+    )?;
+    
+    // Extensions must be initialized before the mint itself
+    if soulbound_metadata.is_some() {
+        invoke(
+            &spl_token_2022::instruction::initialize_non_transferable_mint(
+                token_program.key,
+                mint.key,
+            )?,
+            &[mint.clone(), token_program.clone()],
+        )?;
+        invoke(
+            &spl_token_2022::extension::metadata_pointer::instruction::initialize(
+                token_program.key,
+                mint.key,
+                Some(*mint_authority.key),
+                Some(*mint.key),
+            )?,
+            &[mint.clone(), token_program.clone()],
+        )?;
+    }
+    
+    // 0 decimals, program PDA as mint and freeze authority
+    invoke(
+        &spl_token_2022::instruction::initialize_mint2(
+            token_program.key,
+            mint.key,
+            mint_authority.key,
+            Some(mint_authority.key),
+            0,
+        )?,
+        &[mint.clone(), token_program.clone()],
+    )?;
+    
+    if let Some(tier_metadata) = soulbound_metadata {
+        invoke_signed(
+            &spl_token_metadata_interface::instruction::initialize(
+                token_program.key,
+                mint.key,
+                mint_authority.key,
+                mint.key,
+                mint_authority.key,
+                tier_metadata.name(),
+                tier_metadata.symbol(),
+                tier_metadata.uri(),
+            ),
+            &[mint.clone(), mint_authority.clone(), token_program.clone()],
+            &[authority_seeds],
+        )?;
+    }
+    
+    // Create the holder's associated token account
+    invoke(
+        &spl_associated_token_account::instruction::create_associated_token_account(
+            payer.key,
+            holder.key,
+            mint.key,
+            token_program.key,
+        ),
+        &[
+            payer.clone(),
+            holder_token_account.clone(),
+            holder.clone(),
+            mint.clone(),
+            system_program_account.clone(),
+            token_program.clone(),
+            associated_token_program.clone(),
+        ],
+    )?;
+    
+    // Mint exactly one token
+    invoke_signed(
+        &spl_token_2022::instruction::mint_to(
+            token_program.key,
+            mint.key,
+            holder_token_account.key,
+            mint_authority.key,
+            &[],
+            1,
+        )?,
+        &[
+            mint.clone(),
+            holder_token_account.clone(),
+            mint_authority.clone(),
+            token_program.clone(),
+        ],
+        &[authority_seeds],
+    )?;
+    
+    // Metaplex master edition fixes legacy supply; Token-2022 mints drop the authority instead
+    if soulbound_metadata.is_some() {
+        invoke_signed(
+            &spl_token_2022::instruction::set_authority(
+                token_program.key,
+                mint.key,
+                None,
+                spl_token_2022::instruction::AuthorityType::MintTokens,
+                mint_authority.key,
+                &[],
+            )?,
+            &[mint.clone(), mint_authority.clone(), token_program.clone()],
+            &[authority_seeds],
+        )?;
+    }
+    Ok(())
+}
+
+/// Token-2022 metadata stored in a soulbound mint for the given tier
+fn token_2022_metadata(
+    tier_metadata: &TierMetadata,
+    mint: &Pubkey,
+    update_authority: &Pubkey,
+) -> TokenMetadata {
+    TokenMetadata {
+        update_authority: Some(*update_authority).try_into().unwrap_or_default(),
+        mint: *mint,
+        name: tier_metadata.name(),
+        symbol: tier_metadata.symbol(),
+        uri: tier_metadata.uri(),
+        additional_metadata: Vec::new(),
+    }
+}
+
+/// Point an access NFT at `tier_metadata`: the mint's own metadata for a
+/// soulbound Token-2022 NFT, the Metaplex metadata account otherwise. The mint
+/// authority PDA signs as update authority; `payer` covers any Token-2022 realloc.
+#[allow(clippy::too_many_arguments)]
+fn update_nft_metadata<'a>(
+    config: &AccessConfig,
+    tier_metadata: &TierMetadata,
+    payer: &AccountInfo<'a>,
+    nft_mint: &AccountInfo<'a>,
+    mint_authority: &AccountInfo<'a>,
+    authority_bump: u8,
+    metadata: &AccountInfo<'a>,
+    system_program_account: &AccountInfo<'a>,
+    token_program: &AccountInfo<'a>,
+    token_metadata_program: &AccountInfo<'a>,
+) -> ProgramResult {
+    if *nft_mint.owner == spl_token_2022::id() {
+        return update_token_2022_metadata(
+            payer,
+            nft_mint,
+            mint_authority,
+            authority_bump,
+            system_program_account,
+            token_program,
+            tier_metadata,
+        );
+    }
+    
+    let authority_seeds: &[&[u8]] = &[MINT_AUTHORITY_SEED, &[authority_bump]];
+    UpdateMetadataAccountV2CpiBuilder::new(token_metadata_program)
+        .metadata(metadata)
+        .update_authority(mint_authority)
+        .data(config.nft_data(tier_metadata, mint_authority.key, true))
+        .invoke_signed(&[authority_seeds])
+}
+
+/// Rewrite a soulbound mint's name/symbol/URI, topping up rent first since
+/// Token-2022 reallocates the metadata in place.
+fn update_token_2022_metadata<'a>(
+    payer: &AccountInfo<'a>,
+    mint: &AccountInfo<'a>,
+    mint_authority: &AccountInfo<'a>,
+    authority_bump: u8,
+    system_program_account: &AccountInfo<'a>,
+    token_program: &AccountInfo<'a>,
+    tier_metadata: &TierMetadata,
+) -> ProgramResult {
+    let current_len = {
+        let data = mint.data.borrow();
+        let state = StateWithExtensions::<Mint2022>::unpack(&data)?;
+        state.get_variable_len_extension::<TokenMetadata>()?.tlv_size_of()?
+    };
+    let new_len = token_2022_metadata(tier_metadata, mint.key, mint_authority.key).tlv_size_of()?;
+    let required = Rent::get()?.minimum_balance(
+        mint.data_len().saturating_sub(current_len).saturating_add(new_len),
+    );
+    let shortfall = required.saturating_sub(mint.lamports());
+    if shortfall > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, mint.key, shortfall),
+            &[payer.clone(), mint.clone(), system_program_account.clone()],
+        )?;
+    }
+    
+    let authority_seeds: &[&[u8]] = &[MINT_AUTHORITY_SEED, &[authority_bump]];
+    for (field, value) in [
+        (Field::Name, tier_metadata.name()),
+        (Field::Symbol, tier_metadata.symbol()),
+        (Field::Uri, tier_metadata.uri()),
+    ] {
+        invoke_signed(
+            &spl_token_metadata_interface::instruction::update_field(
+                token_program.key,
+                mint.key,
+                mint_authority.key,
+                field,
+                value,
+            ),
+            &[mint.clone(), mint_authority.clone(), token_program.clone()],
+            &[authority_seeds],
+        )?;
+    }
+    Ok(())
+}
+
+/// CPI into Token Metadata for the metadata account and a max-supply-0 master
+/// edition; the mint authority PDA signs as mint and update authority.
+#[allow(clippy::too_many_arguments)]
+fn create_metadata_and_master_edition<'a>(
+    payer: &AccountInfo<'a>,
+    mint: &AccountInfo<'a>,
+    mint_authority: &AccountInfo<'a>,
+    authority_bump: u8,
+    metadata: &AccountInfo<'a>,
+    master_edition: &AccountInfo<'a>,
+    system_program_account: &AccountInfo<'a>,
+    token_program: &AccountInfo<'a>,
+    token_metadata_program: &AccountInfo<'a>,
+    data: DataV2,
+    collection_details: Option<CollectionDetails>,
+) -> ProgramResult {
+    if *metadata.key != Metadata::find_pda(mint.key).0 {
+        msg!("Invalid metadata account");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if *master_edition.key != MasterEdition::find_pda(mint.key).0 {
+        msg!("Invalid master edition account");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    
+    let authority_seeds: &[&[u8]] = &[MINT_AUTHORITY_SEED, &[authority_bump]];
+    
+    let mut create_metadata = CreateMetadataAccountV3CpiBuilder::new(token_metadata_program);
+    create_metadata
+        .metadata(metadata)
+        .mint(mint)
+        .mint_authority(mint_authority)
+        .payer(payer)
+        .update_authority(mint_authority, true)
+        .system_program(system_program_account)
+        .data(data)
+        .is_mutable(true);
+    if let Some(collection_details) = collection_details {
+        create_metadata.collection_details(collection_details);
+    }
+    create_metadata.invoke_signed(&[authority_seeds])?;
+    
+    // Hands mint and freeze authority to the edition PDA, fixing supply at 1
+    CreateMasterEditionV3CpiBuilder::new(token_metadata_program)
+        .edition(master_edition)
+        .mint(mint)
+        .update_authority(mint_authority)
+        .mint_authority(mint_authority)
+        .payer(payer)
+        .metadata(metadata)
+        .token_program(token_program)
+        .system_program(system_program_account)
+        .max_supply(0)
+        .invoke_signed(&[authority_seeds])?;
+    Ok(())
+}
+
+/// Require the program's upgrade authority to sign, for one-off migrations
+fn check_upgrade_authority(
+    program_id: &Pubkey,
+    program_data: &AccountInfo,
+    upgrade_authority: &AccountInfo,
+) -> ProgramResult {
+    let (expected_program_data, _) = Pubkey::find_program_address(
+        &[program_id.as_ref()],
+        &bpf_loader_upgradeable::id(),
+    );
+    if *program_data.key != expected_program_data || *program_data.owner != bpf_loader_upgradeable::id() {
+        msg!("Invalid program data account");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    
+    let metadata_len = UpgradeableLoaderState::size_of_programdata_metadata();
+    let data = program_data.data.borrow();
+    if data.len() < metadata_len {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let state: UpgradeableLoaderState = limited_deserialize(&data[..metadata_len], metadata_len as u64)
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+    match state {
+        UpgradeableLoaderState::ProgramData { upgrade_authority_address: Some(authority), .. }
+            if authority == *upgrade_authority.key && upgrade_authority.is_signer => Ok(()),
+        _ => {
+            msg!("Only the program upgrade authority can migrate the config");
+            Err(ProgramError::MissingRequiredSignature)
+        }
+    }
+}
+
+fn process_migrate_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    bump: u8,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let legacy_config = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let upgrade_authority = next_account_info(accounts_iter)?;
+    let program_data = next_account_info(accounts_iter)?;
+    let token_mint = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+    
+    check_upgrade_authority(program_id, program_data, upgrade_authority)?;
+    
+    if legacy_config.owner != program_id || legacy_config.data_len() != LegacyAccessConfig::LEN {
+        msg!("Not a legacy access config");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let legacy = LegacyAccessConfig::try_from_slice(&legacy_config.data.borrow())?;
+    if !legacy.is_initialized {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    
+    let (expected_config, expected_bump) = Pubkey::find_program_address(&[CONFIG_SEED], program_id);
+    if *config_account.key != expected_config || bump != expected_bump {
+        msg!("Invalid access config PDA");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    
+    if *token_mint.key != legacy.token_mint || token_mint.owner != &spl_token::id() {
+        msg!("Token mint does not match the legacy config");
+        return Err(ProgramError::InvalidArgument);
+    }
+    let mint_data = Mint::unpack(&token_mint.data.borrow())?;
+    let config = AccessConfig::new(legacy.authority, legacy.token_mint, mint_data.decimals, bump)?;
+    
+    create_pda_account(
+        program_id,
+        upgrade_authority,
+        config_account,
+        system_program_account,
+        AccessConfig::LEN,
+        &[CONFIG_SEED, &[bump]],
+    )?;
+    config.serialize(&mut &mut config_account.data.borrow_mut()[..])?;
+    
+    // Close the legacy config so it can never be read again
+    let legacy_lamports = legacy_config.lamports();
+    **upgrade_authority.lamports.borrow_mut() = upgrade_authority
+        .lamports()
+        .checked_add(legacy_lamports)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    **legacy_config.lamports.borrow_mut() = 0;
+    legacy_config.data.borrow_mut().fill(0);
+    
+    msg!("Legacy access config {} migrated to {}", legacy_config.key, config_account.key);
+    Ok(())
+}
+
+fn process_migrate_access(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let access_account = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+    
+    if !payer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    if *system_program_account.key != system_program::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    
+    if access_account.owner != program_id {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    if access_account.data_len() != LegacyAccessAccount::LEN {
+        msg!("Access account is not in the legacy layout");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let legacy = LegacyAccessAccount::try_from_slice(&access_account.data.borrow())?;
+    if !legacy.is_initialized {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    
+    let (expected_pda, _) = Pubkey::find_program_address(
+        &[ACCESS_SEED, legacy.owner.as_ref()],
+        program_id,
+    );
+    if *access_account.key != expected_pda {
+        msg!("Invalid access account PDA");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    
+    let top_up = Rent::get()?
+        .minimum_balance(AccessAccount::LEN)
+        .saturating_sub(access_account.lamports());
+    if top_up > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, access_account.key, top_up),
+            &[payer.clone(), access_account.clone(), system_program_account.clone()],
+        )?;
+    }
+    access_account.realloc(AccessAccount::LEN, true)?;
+    
+    // The verified tier carries over and goes stale on the config's validity window as usual
+    let access_data = AccessAccount {
+        is_initialized: true,
+        owner: legacy.owner,
+        current_tier: legacy.current_tier,
+        last_verified_balance: legacy.last_verified_balance,
+        verification_timestamp: legacy.verification_timestamp,
+        nft_mint: Pubkey::default(),
+        nft_tier: AccessTier::None,
+        nft_generation: 0,
+        pending_tier: AccessTier::None,
+        pending_slot: 0,
+        checkpoints: [BalanceCheckpoint::default(); TWAB_CHECKPOINTS],
+        checkpoint_head: 0,
+        holdings_hash: [0; 32],
+        subscription_tier: AccessTier::None,
+        subscription_expiry: 0,
+        downgrade_pending_since: 0,
+        linked_wallets: [Pubkey::default(); MAX_LINKED_WALLETS],
+        granted_tier: AccessTier::None,
+        grant_expiry: 0,
+        bump: legacy.bump,
+    };
+    
+    access_data.serialize(&mut &mut access_account.data.borrow_mut()[..])?;
+    msg!("Access account migrated for {}", legacy.owner);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    const DAY: i64 = 24 * 60 * 60;
+    const START: i64 = 1_700_000_000;
+    
+    fn config() -> AccessConfig {
+        AccessConfig::new(Pubkey::new_unique(), Pubkey::new_unique(), 0, 255).unwrap()
+    }
+    
+    fn access() -> AccessAccount {
+        AccessAccount {
+            is_initialized: true,
+            owner: Pubkey::new_unique(),
+            current_tier: AccessTier::None,
+            last_verified_balance: 0,
+            verification_timestamp: 0,
+            nft_mint: Pubkey::default(),
+            nft_tier: AccessTier::None,
+            nft_generation: 0,
+            pending_tier: AccessTier::None,
+            pending_slot: 0,
+            checkpoints: [BalanceCheckpoint::default(); TWAB_CHECKPOINTS],
+            checkpoint_head: 0,
+            holdings_hash: [0; 32],
+            subscription_tier: AccessTier::None,
+            subscription_expiry: 0,
+            downgrade_pending_since: 0,
+            linked_wallets: [Pubkey::default(); MAX_LINKED_WALLETS],
+            granted_tier: AccessTier::None,
+            grant_expiry: 0,
+            bump: 255,
+        }
+    }
+    
+    #[test]
+    fn record_balance_without_window_uses_current_balance() {
+        let config = config();
+        let mut account = access();
+        assert_eq!(account.record_balance(5_000, &config, START), 5_000);
+        assert_eq!(account.record_balance(100, &config, START + DAY), 100);
+    }
+    
+    #[test]
+    fn twab_counts_time_before_first_checkpoint_as_zero() {
+        let mut config = config();
+        config.twab_window = 8 * DAY;
+        let mut account = access();
+        assert_eq!(account.record_balance(1_000, &config, START), 0);
+        assert_eq!(account.twab(8 * DAY, START + 4 * DAY), 500);
+        assert_eq!(account.twab(8 * DAY, START + 8 * DAY), 1_000);
+        assert_eq!(account.twab(0, START + 8 * DAY), 0);
+    }
+    
+    #[test]
+    fn record_balance_ignores_a_deposit_made_just_before_verifying() {
+        let mut config = config();
+        config.twab_window = 7 * DAY;
+        let mut account = access();
+        account.record_balance(1_000, &config, START);
+        // A week later the user briefly holds 1000x as much; the average barely moves
+        assert_eq!(account.record_balance(1_000_000, &config, START + 7 * DAY), 1_000);
+        assert_eq!(account.record_balance(1_000_000, &config, START + 7 * DAY + 60), 1_099);
+    }
+    
+    #[test]
+    fn record_balance_never_exceeds_current_balance() {
+        let mut config = config();
+        config.twab_window = 7 * DAY;
+        let mut account = access();
+        account.record_balance(10_000, &config, START);
+        assert_eq!(account.record_balance(10, &config, START + 7 * DAY), 10);
+    }
+    
+    #[test]
+    fn record_balance_folds_close_reads_into_the_lower_balance() {
+        let mut config = config();
+        config.twab_window = 7 * 100;
+        let mut account = access();
+        account.record_balance(500, &config, START);
+        account.record_balance(300, &config, START + 10);
+        account.record_balance(900, &config, START + 20);
+        assert_eq!(account.checkpoint_head, 1);
+        assert_eq!(account.checkpoints[0].balance, 300);
+        
+        account.record_balance(900, &config, START + 100);
+        assert_eq!(account.checkpoint_head, 2);
+    }
+    
+    #[test]
+    fn record_balance_short_window_cannot_be_flushed_within_a_second() {
+        let mut config = config();
+        config.twab_window = 3;
+        let mut account = access();
+        account.record_balance(1_000, &config, START);
+        // Repeated reads in the same second fold instead of pushing older checkpoints out
+        for _ in 0..TWAB_CHECKPOINTS {
+            account.record_balance(1_000_000, &config, START + 3);
+        }
+        assert_eq!(account.checkpoint_head, 2);
+        assert_eq!(account.record_balance(1_000_000, &config, START + 3), 1_000);
+    }
+    
+    #[test]
+    fn record_balance_ring_keeps_the_latest_window() {
+        let mut config = config();
+        config.twab_window = 7 * DAY;
+        let mut account = access();
+        for day in 0..20 {
+            account.record_balance(1_000, &config, START + day * DAY);
+        }
+        // Oldest checkpoints are overwritten but the window is still fully covered
+        assert_eq!(account.twab(7 * DAY, START + 20 * DAY), 1_000);
+        assert_eq!(account.checkpoint_head as usize, 20 % TWAB_CHECKPOINTS);
+    }
+    
+    #[test]
+    fn soften_downgrade_passes_rises_and_clears_the_timer() {
+        let config = config();
+        let mut account = access();
+        account.downgrade_pending_since = START;
+        let tier = account.soften_downgrade(6_000, AccessTier::Pro, &AccessTier::Basic, &config, START + 1);
+        assert_eq!(tier, AccessTier::Pro);
+        assert_eq!(account.downgrade_pending_since, 0);
+    }
+    
+    #[test]
+    fn soften_downgrade_holds_within_the_hysteresis_band() {
+        let mut config = config();
+        config.hysteresis_bps = 1_000;
+        let mut account = access();
+        // 10% below the 5,000 Pro threshold still holds Pro
+        let tier = account.soften_downgrade(4_500, AccessTier::Basic, &AccessTier::Pro, &config, START);
+        assert_eq!(tier, AccessTier::Pro);
+        let tier = account.soften_downgrade(4_499, AccessTier::Basic, &AccessTier::Pro, &config, START);
+        assert_eq!(tier, AccessTier::Basic);
+    }
+    
+    #[test]
+    fn soften_downgrade_never_raises_through_the_band() {
+        let mut config = config();
+        config.hysteresis_bps = 10_000;
+        let mut account = access();
+        // A 100% band zeroes every threshold, but a reading can at most hold the current tier
+        let tier = account.soften_downgrade(0, AccessTier::None, &AccessTier::Pro, &config, START);
+        assert_eq!(tier, AccessTier::Pro);
+    }
+    
+    #[test]
+    fn soften_downgrade_holds_the_tier_through_the_grace_period() {
+        let mut config = config();
+        config.downgrade_grace_period = DAY;
+        let mut account = access();
+        let tier = account.soften_downgrade(4_000, AccessTier::Basic, &AccessTier::Pro, &config, START);
+        assert_eq!(tier, AccessTier::Pro);
+        assert_eq!(account.downgrade_pending_since, START);
+        
+        // Later low readings keep the original start time
+        let tier = account.soften_downgrade(0, AccessTier::None, &AccessTier::Pro, &config, START + DAY - 1);
+        assert_eq!(tier, AccessTier::Pro);
+        assert_eq!(account.downgrade_pending_since, START);
+        
+        let tier = account.soften_downgrade(0, AccessTier::None, &AccessTier::Pro, &config, START + DAY);
+        assert_eq!(tier, AccessTier::None);
+        assert_eq!(account.downgrade_pending_since, 0);
+    }
+    
+    #[test]
+    fn soften_downgrade_recovery_restarts_the_grace_period() {
+        let mut config = config();
+        config.downgrade_grace_period = DAY;
+        let mut account = access();
+        account.soften_downgrade(4_000, AccessTier::Basic, &AccessTier::Pro, &config, START);
+        account.soften_downgrade(5_000, AccessTier::Pro, &AccessTier::Pro, &config, START + 10);
+        assert_eq!(account.downgrade_pending_since, 0);
+        
+        let tier = account.soften_downgrade(4_000, AccessTier::Basic, &AccessTier::Pro, &config, START + DAY);
+        assert_eq!(tier, AccessTier::Pro);
+        assert_eq!(account.downgrade_pending_since, START + DAY);
+    }
+    
+    #[test]
+    fn soften_downgrade_without_policy_drops_at_once() {
+        let config = config();
+        let mut account = access();
+        let tier = account.soften_downgrade(999, AccessTier::None, &AccessTier::Elite, &config, START);
+        assert_eq!(tier, AccessTier::None);
+        assert_eq!(account.downgrade_pending_since, 0);
+    }
+    
+    fn clock(slot: u64, unix_timestamp: i64) -> Clock {
+        Clock {
+            slot,
+            unix_timestamp,
+            ..Clock::default()
+        }
+    }
+    
+    #[test]
+    fn apply_verification_holds_a_rise_until_the_slot_gap() {
+        let config = config();
+        let mut account = access();
+        let tier = account.apply_verification(30_000, AccessTier::Elite, &config, &clock(100, START));
+        assert_eq!(tier, AccessTier::None);
+        assert_eq!(account.pending_tier, AccessTier::Elite);
+        assert_eq!(account.pending_slot, 100);
+        
+        let tier = account.apply_verification(30_000, AccessTier::Elite, &config, &clock(249, START + 60));
+        assert_eq!(tier, AccessTier::None);
+        assert_eq!(account.pending_slot, 100);
+        
+        // Confirmed at the lower of the two readings
+        let tier = account.apply_verification(6_000, AccessTier::Pro, &config, &clock(250, START + 120));
+        assert_eq!(tier, AccessTier::Pro);
+        assert_eq!(account.current_tier, AccessTier::Pro);
+        assert_eq!(account.pending_tier, AccessTier::None);
+        assert_eq!(account.pending_slot, 0);
+        assert_eq!(account.last_verified_balance, 6_000);
+        assert_eq!(account.verification_timestamp, START + 120);
+    }
+    
+    #[test]
+    fn apply_verification_pending_tier_tracks_the_lowest_reading() {
+        let config = config();
+        let mut account = access();
+        account.apply_verification(30_000, AccessTier::Elite, &config, &clock(100, START));
+        account.apply_verification(1_000, AccessTier::Basic, &config, &clock(150, START));
+        assert_eq!(account.pending_tier, AccessTier::Basic);
+        let tier = account.apply_verification(30_000, AccessTier::Elite, &config, &clock(400, START));
+        assert_eq!(tier, AccessTier::Basic);
+    }
+    
+    #[test]
+    fn apply_verification_drops_at_once_and_clears_a_pending_rise() {
+        let config = config();
+        let mut account = access();
+        account.current_tier = AccessTier::Pro;
+        account.verification_timestamp = START;
+        account.apply_verification(30_000, AccessTier::Elite, &config, &clock(100, START));
+        assert_eq!(account.pending_tier, AccessTier::Elite);
+        
+        let tier = account.apply_verification(1_000, AccessTier::Basic, &config, &clock(101, START));
+        assert_eq!(tier, AccessTier::Basic);
+        assert_eq!(account.pending_tier, AccessTier::None);
+    }
+    
+    #[test]
+    fn apply_verification_without_slot_gap_rises_at_once() {
+        let mut config = config();
+        config.verification_slot_gap = 0;
+        let mut account = access();
+        let tier = account.apply_verification(30_000, AccessTier::Elite, &config, &clock(100, START));
+        assert_eq!(tier, AccessTier::Elite);
+        assert_eq!(account.pending_tier, AccessTier::None);
+    }
+    
+    #[test]
+    fn apply_verification_treats_a_stale_tier_as_none() {
+        let config = config();
+        let mut account = access();
+        account.current_tier = AccessTier::Elite;
+        account.verification_timestamp = START;
+        let now = START + config.verification_validity + 1;
+        // Re-verifying the same balance after expiry has to pass the slot gap again
+        let tier = account.apply_verification(30_000, AccessTier::Elite, &config, &clock(100, now));
+        assert_eq!(tier, AccessTier::None);
+        assert_eq!(account.pending_tier, AccessTier::Elite);
+    }
+    
+    fn usage(period_start: i64) -> UsageAccount {
+        UsageAccount {
+            is_initialized: true,
+            owner: Pubkey::new_unique(),
+            period_start,
+            used: [7; MAX_FEATURES],
+            bump: 255,
+        }
+    }
+    
+    #[test]
+    fn roll_period_keeps_usage_within_the_period() {
+        let mut account = usage(START);
+        account.roll_period(START + USAGE_PERIOD - 1);
+        assert_eq!(account.period_start, START);
+        assert_eq!(account.used, [7; MAX_FEATURES]);
+    }
+    
+    #[test]
+    fn roll_period_resets_usage_on_the_boundary() {
+        let mut account = usage(START);
+        account.roll_period(START + USAGE_PERIOD);
+        assert_eq!(account.period_start, START + USAGE_PERIOD);
+        assert_eq!(account.used, [0; MAX_FEATURES]);
+    }
+    
+    #[test]
+    fn roll_period_stays_aligned_after_skipped_periods() {
+        let mut account = usage(START);
+        account.roll_period(START + 3 * USAGE_PERIOD + DAY);
+        assert_eq!(account.period_start, START + 3 * USAGE_PERIOD);
+        assert_eq!(account.used, [0; MAX_FEATURES]);
+    }
+    
+    #[test]
+    fn roll_period_ignores_a_clock_behind_the_period() {
+        let mut account = usage(START);
+        account.roll_period(START - DAY);
+        assert_eq!(account.period_start, START);
+        assert_eq!(account.used, [7; MAX_FEATURES]);
+    }
+    
+    #[test]
+    fn default_quotas_meter_only_custom_bots() {
+        let registry = FeatureRegistry {
+            is_initialized: true,
+            config: Pubkey::new_unique(),
+            feature_tiers: FeatureRegistry::default_feature_tiers(),
+            feature_quotas: FeatureRegistry::default_feature_quotas(),
+            bump: 255,
+        };
+        assert_eq!(registry.quota(FEATURE_CUSTOM_BOTS, &AccessTier::Pro), 25);
+        assert_eq!(registry.quota(FEATURE_CUSTOM_BOTS, &AccessTier::Elite), 100);
+        assert_eq!(registry.quota(FEATURE_SNIPER_BOT, &AccessTier::Elite), 0);
+        assert_eq!(registry.quota(MAX_FEATURES as u8, &AccessTier::Elite), 0);
+    }
+    
+    #[test]
+    fn dregan_equivalent_weighs_accepted_mints() {
+        let mut config = config();
+        let lp_mint = Pubkey::new_unique();
+        let receipt_mint = Pubkey::new_unique();
+        config.weighted_mints[0] = WeightedMint { mint: lp_mint, weight: 2 * WEIGHT_SCALE };
+        config.weighted_mints[2] = WeightedMint { mint: receipt_mint, weight: WEIGHT_SCALE / 2 };
+        
+        assert_eq!(config.dregan_equivalent(&config.token_mint, 1_234), Some(1_234));
+        assert_eq!(config.dregan_equivalent(&lp_mint, 1_234), Some(2_468));
+        assert_eq!(config.dregan_equivalent(&receipt_mint, 1_235), Some(617));
+        assert_eq!(config.dregan_equivalent(&lp_mint, u64::MAX), Some(u64::MAX));
+    }
+    
+    #[test]
+    fn dregan_equivalent_rejects_other_mints() {
+        let config = config();
+        assert_eq!(config.dregan_equivalent(&Pubkey::new_unique(), 1_000), None);
+        // Empty slots hold the default pubkey and must not accept it
+        assert_eq!(config.dregan_equivalent(&Pubkey::default(), 1_000), None);
+    }
+}
@@ -1,25 +1,32 @@
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
-    entrypoint,
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
     entrypoint::ProgramResult,
     msg,
+    log::sol_log_data,
     program::{invoke, invoke_signed},
     program_error::ProgramError,
     program_pack::Pack,
+    program_utils::limited_deserialize,
     pubkey::Pubkey,
     clock::Clock,
+    system_instruction,
+    system_program,
     sysvar::Sysvar,
     rent::Rent,
 };
+#[cfg(not(feature = "no-entrypoint"))]
+use solana_program::entrypoint;
 use borsh::{BorshDeserialize, BorshSerialize};
 use spl_token::state::Account as TokenAccount;
 
 // DREGAN Staking Pool - Fixed Version with Actual Token Transfers
-// Tiers: 30-day (10% APY), 60-day (15% APY), 90-day (20% APY)
+// Tiers: 30-day (10% APY), 60-day (15% APY), 90-day (20% APY), Flexible no-lock (5% APY)
 
 solana_program::declare_id!("8nEE9CgLAEMmVmN5R4tdPuVhJLp4sU9i87QiFVXcdwKP");
 
 // Seeds for PDA derivation
+pub const POOL_SEED: &[u8] = b"pool";
 pub const STAKE_SEED: &[u8] = b"stake";
 pub const VAULT_SEED: &[u8] = b"vault";
 pub const REWARD_VAULT_SEED: &[u8] = b"reward_vault";
@@ -29,6 +36,7 @@ pub enum StakeTier {
     Basic,  // 30 days, 10% APY
     Pro,    // 60 days, 15% APY
     Elite,  // 90 days, 20% APY
+    Flexible,  // No lock, 5% APY
 }
 
 impl StakeTier {
@@ -37,6 +45,7 @@ impl StakeTier {
             StakeTier::Basic => 30 * 24 * 60 * 60,  // 30 days
             StakeTier::Pro => 60 * 24 * 60 * 60,    // 60 days
             StakeTier::Elite => 90 * 24 * 60 * 60,  // 90 days
+            StakeTier::Flexible => 0,  // Unstake any time
         }
     }
     
@@ -45,10 +54,27 @@ impl StakeTier {
             StakeTier::Basic => 1000,  // 10%
             StakeTier::Pro => 1500,    // 15%
             StakeTier::Elite => 2000,  // 20%
+            StakeTier::Flexible => 500,  // 5%
+        }
+    }
+    
+    /// Index into the per-tier arrays on PoolConfig
+    pub fn index(&self) -> usize {
+        match self {
+            StakeTier::Basic => 0,
+            StakeTier::Pro => 1,
+            StakeTier::Elite => 2,
+            StakeTier::Flexible => 3,
         }
     }
 }
 
+pub const TIER_COUNT: usize = 4;
+
+/// How long a Flexible position accrues before it has to be restaked. Gives
+/// Flexible a finite reward the pool can reserve up front like the locked tiers.
+pub const FLEXIBLE_ACCRUAL_TERM: i64 = 365 * 24 * 60 * 60;
+
 #[derive(BorshSerialize, BorshDeserialize, Debug)]
 pub struct StakeAccount {
     pub is_initialized: bool,
@@ -58,27 +84,49 @@ pub struct StakeAccount {
     pub stake_timestamp: i64,
     pub unlock_timestamp: i64,
     pub claimed_rewards: u64,
+    pub accrual_end_timestamp: i64,
+    pub unbonding_until: i64,
     pub bump: u8,
 }
 
 impl StakeAccount {
-    pub const LEN: usize = 1 + 32 + 8 + 1 + 8 + 8 + 8 + 1; // 67 bytes
+    pub const LEN: usize = 1 + 32 + 8 + 1 + 8 + 8 + 8 + 8 + 8 + 1; // 83 bytes
+    
+    /// Lock is over at `unlock_timestamp`, or for everyone once the campaign ends
+    pub fn lock_expired(&self, current_time: i64, campaign_end: i64) -> bool {
+        current_time >= self.unlock_timestamp || current_time >= campaign_end
+    }
     
     pub fn calculate_rewards(&self, current_time: i64) -> u64 {
         if self.amount == 0 || self.stake_timestamp == 0 {
             return 0;
         }
-        let staking_duration = (current_time - self.stake_timestamp).max(0) as u64;
-        let seconds_per_year: u64 = 365 * 24 * 60 * 60;
-        let apy = self.tier.apy_basis_points();
-        // rewards = amount * apy * duration / (seconds_per_year * 10000)
-        // Using checked math to prevent overflow
-        self.amount
-            .checked_mul(apy)
-            .and_then(|v| v.checked_mul(staking_duration))
-            .and_then(|v| v.checked_div(seconds_per_year))
-            .and_then(|v| v.checked_div(10000))
-            .unwrap_or(0)
+        // No accrual past the accrual end fixed at stake time (unlock or the Flexible
+        // term, or campaign end if sooner)
+        let accrual_time = current_time.min(self.accrual_end_timestamp);
+        let staking_duration = (accrual_time - self.stake_timestamp).max(0) as u128;
+        let seconds_per_year: u128 = 365 * 24 * 60 * 60;
+        let apy = self.tier.apy_basis_points() as u128;
+        // rewards = amount * apy * duration / (seconds_per_year * 10000) in u128;
+        // amount * apy always fits, and only a duration of centuries can saturate
+        let rewards = ((self.amount as u128) * apy).saturating_mul(staking_duration) / (seconds_per_year * 10000);
+        u64::try_from(rewards).unwrap_or(u64::MAX)
+    }
+    
+    /// Rewards still reserved for this position: the full reward up to
+    /// `accrual_end_timestamp` minus what has already been claimed, which
+    /// covers everything the position can ever claim.
+    pub fn reserved_rewards(&self) -> u64 {
+        self.calculate_rewards(self.accrual_end_timestamp)
+            .saturating_sub(self.claimed_rewards)
+    }
+    
+    /// Pull the accrual end in to `end` if that is sooner, returning the
+    /// reservation this frees up
+    pub fn clip_accrual_end(&mut self, end: i64) -> u64 {
+        let reserved_before = self.reserved_rewards();
+        self.accrual_end_timestamp = self.accrual_end_timestamp.min(end);
+        reserved_before.saturating_sub(self.reserved_rewards())
     }
 }
 
@@ -91,17 +139,127 @@ pub struct PoolConfig {
     pub reward_vault: Pubkey,
     pub total_staked: u64,
     pub total_rewards_distributed: u64,
+    pub reward_liabilities: u64,
+    pub min_stake_amount: u64,
+    pub max_stake_amount: u64,
+    pub tier_caps: [u64; TIER_COUNT],
+    pub tier_staked: [u64; TIER_COUNT],
+    pub campaign_start: i64,
+    pub deposit_end: i64,
+    pub campaign_end: i64,
+    pub unbonding_cooldowns: [i64; TIER_COUNT],
+    /// Stake still held in legacy positions that MigrateStake has not reserved yet
+    pub unmigrated_staked: u64,
     pub bump: u8,
 }
 
 impl PoolConfig {
+    pub const LEN: usize = 1 + 32 + 32 + 32 + 32 + 8 + 8 + 8 + 8 + 8
+        + 8 * TIER_COUNT + 8 * TIER_COUNT + 8 + 8 + 8 + 8 * TIER_COUNT + 8 + 1; // 298 bytes
+}
+
+/// StakeAccount as written before accrual ends and unbonding were tracked.
+/// Only read by MigrateStake.
+#[derive(BorshDeserialize, Debug)]
+pub struct LegacyStakeAccount {
+    pub is_initialized: bool,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub tier: StakeTier,
+    pub stake_timestamp: i64,
+    pub unlock_timestamp: i64,
+    pub claimed_rewards: u64,
+    pub bump: u8,
+}
+
+impl LegacyStakeAccount {
+    pub const LEN: usize = 1 + 32 + 8 + 1 + 8 + 8 + 8 + 1; // 67 bytes
+}
+
+/// PoolConfig as written before reward liabilities, limits, the campaign and
+/// cooldowns were tracked, when the pool could live at any address and the vault
+/// authority was [VAULT_SEED]. Only read by MigratePool.
+#[derive(BorshDeserialize, Debug)]
+pub struct LegacyPoolConfig {
+    pub is_initialized: bool,
+    pub authority: Pubkey,
+    pub token_mint: Pubkey,
+    pub stake_vault: Pubkey,
+    pub reward_vault: Pubkey,
+    pub total_staked: u64,
+    pub total_rewards_distributed: u64,
+    pub bump: u8,
+}
+
+impl LegacyPoolConfig {
     pub const LEN: usize = 1 + 32 + 32 + 32 + 32 + 8 + 8 + 1; // 146 bytes
 }
 
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub enum StakeEvent {
+    SurplusRewardsWithdrawn {
+        authority: Pubkey,
+        destination: Pubkey,
+        amount: u64,
+        remaining_surplus: u64,
+    },
+    ExcessSwept {
+        authority: Pubkey,
+        destination: Pubkey,
+        amount: u64,
+    },
+    PoolAudited {
+        stake_vault_balance: u64,
+        total_staked: u64,
+        reward_vault_balance: u64,
+        reward_liabilities: u64,
+    },
+    StakeLimitsUpdated {
+        authority: Pubkey,
+        min_stake_amount: u64,
+        max_stake_amount: u64,
+        tier_caps: [u64; TIER_COUNT],
+    },
+    CampaignUpdated {
+        authority: Pubkey,
+        campaign_start: i64,
+        deposit_end: i64,
+        campaign_end: i64,
+    },
+    UnbondingCooldownsUpdated {
+        authority: Pubkey,
+        unbonding_cooldowns: [i64; TIER_COUNT],
+    },
+    UnstakeRequested {
+        owner: Pubkey,
+        amount: u64,
+        withdrawable_at: i64,
+    },
+    PoolMigrated {
+        legacy_pool: Pubkey,
+        pool_config: Pubkey,
+        total_staked: u64,
+    },
+    StakeMigrated {
+        owner: Pubkey,
+        amount: u64,
+        reserved_rewards: u64,
+    },
+}
+
+impl StakeEvent {
+    /// Emit the event as base64 program data in the transaction logs
+    pub fn emit(&self) -> ProgramResult {
+        sol_log_data(&[&self.try_to_vec()?]);
+        Ok(())
+    }
+}
+
 #[derive(BorshSerialize, BorshDeserialize, Debug)]
 pub enum StakeInstruction {
-    /// Initialize the staking pool
-    /// Accounts: [pool_config, authority, token_mint, stake_vault, reward_vault, system_program, token_program, rent]
+    /// Create the staking pool at its PDA ([POOL_SEED]); both vaults must be
+    /// token accounts of `token_mint` owned by the pool's vault authority ([VAULT_SEED, pool_config])
+    /// Accounts: [pool_config, authority, token_mint, stake_vault, reward_vault, system_program]
     InitializePool { bump: u8 },
     
     /// Initialize a user stake account
@@ -112,15 +270,67 @@ pub enum StakeInstruction {
     /// Accounts: [stake_account, owner, user_token_account, stake_vault, pool_config, token_program]
     Stake { amount: u64, tier: StakeTier },
     
-    /// Unstake tokens (after lock period)
+    /// Unstake tokens (after lock period, any time for Flexible) when the tier has no cooldown
+    /// or the campaign has ended
     /// Accounts: [stake_account, owner, user_token_account, stake_vault, pool_config, vault_authority, token_program]
     Unstake,
     
     /// Claim staking rewards
     /// Accounts: [stake_account, owner, user_token_account, reward_vault, pool_config, vault_authority, token_program]
     ClaimRewards,
+    
+    /// Withdraw reward vault tokens not reserved for open positions (authority only)
+    /// Accounts: [pool_config, authority, reward_vault, destination_token_account, vault_authority, token_program]
+    WithdrawSurplusRewards { amount: u64 },
+    
+    /// Recover stake vault tokens in excess of total_staked (authority only)
+    /// Accounts: [pool_config, authority, stake_vault, destination_token_account, vault_authority, token_program]
+    SweepExcess,
+    
+    /// Assert the vaults cover total_staked and reward liabilities (permissionless)
+    /// Accounts: [pool_config, stake_vault, reward_vault]
+    AuditPool,
+    
+    /// Set per-position stake bounds and per-tier total caps (authority only)
+    /// Accounts: [pool_config, authority]
+    SetStakeLimits {
+        min_stake_amount: u64,
+        max_stake_amount: u64,
+        tier_caps: [u64; TIER_COUNT],
+    },
+    
+    /// Set the campaign window: deposits open [campaign_start, deposit_end],
+    /// rewards accrue until campaign_end (authority only)
+    /// Accounts: [pool_config, authority]
+    SetCampaign {
+        campaign_start: i64,
+        deposit_end: i64,
+        campaign_end: i64,
+    },
+    
+    /// Set the per-tier unbonding cooldown in seconds (authority only)
+    /// Accounts: [pool_config, authority]
+    SetUnbondingCooldowns { unbonding_cooldowns: [i64; TIER_COUNT] },
+    
+    /// Start the unbonding cooldown once the lock has expired; the position stops earning
+    /// Accounts: [stake_account, owner, pool_config]
+    RequestUnstake,
+    
+    /// Withdraw an unbonded position after its cooldown, or at once after the campaign ends
+    /// Accounts: [stake_account, owner, user_token_account, stake_vault, pool_config, vault_authority, token_program]
+    WithdrawUnstaked,
+    
+    /// Move a pre-PDA pool to the pool PDA, hand both vaults to the new vault
+    /// authority and close the old account (program upgrade authority only)
+    /// Accounts: [legacy_pool, pool_config, upgrade_authority, program_data, stake_vault, reward_vault, legacy_vault_authority, token_program, system_program]
+    MigratePool { bump: u8 },
+    
+    /// Grow a legacy stake account to the current layout and reserve its rewards (permissionless)
+    /// Accounts: [stake_account, pool_config, payer, system_program]
+    MigrateStake,
 }
 
+#[cfg(not(feature = "no-entrypoint"))]
 entrypoint!(process_instruction);
 
 pub fn process_instruction(
@@ -146,13 +356,127 @@ pub fn process_instruction(
         }
         StakeInstruction::Unstake => {
             msg!("DREGAN Staking: Unstake");
-            process_unstake(program_id, accounts)
+            process_unstake(program_id, accounts, false)
         }
         StakeInstruction::ClaimRewards => {
             msg!("DREGAN Staking: Claim Rewards");
             process_claim_rewards(program_id, accounts)
         }
+        StakeInstruction::WithdrawSurplusRewards { amount } => {
+            msg!("DREGAN Staking: Withdraw Surplus Rewards {}", amount);
+            process_withdraw_surplus_rewards(program_id, accounts, amount)
+        }
+        StakeInstruction::SweepExcess => {
+            msg!("DREGAN Staking: Sweep Excess");
+            process_sweep_excess(program_id, accounts)
+        }
+        StakeInstruction::AuditPool => {
+            msg!("DREGAN Staking: Audit Pool");
+            process_audit_pool(program_id, accounts)
+        }
+        StakeInstruction::SetStakeLimits { min_stake_amount, max_stake_amount, tier_caps } => {
+            msg!("DREGAN Staking: Set Stake Limits");
+            process_set_stake_limits(program_id, accounts, min_stake_amount, max_stake_amount, tier_caps)
+        }
+        StakeInstruction::SetCampaign { campaign_start, deposit_end, campaign_end } => {
+            msg!("DREGAN Staking: Set Campaign");
+            process_set_campaign(program_id, accounts, campaign_start, deposit_end, campaign_end)
+        }
+        StakeInstruction::SetUnbondingCooldowns { unbonding_cooldowns } => {
+            msg!("DREGAN Staking: Set Unbonding Cooldowns");
+            process_set_unbonding_cooldowns(program_id, accounts, unbonding_cooldowns)
+        }
+        StakeInstruction::RequestUnstake => {
+            msg!("DREGAN Staking: Request Unstake");
+            process_request_unstake(program_id, accounts)
+        }
+        StakeInstruction::WithdrawUnstaked => {
+            msg!("DREGAN Staking: Withdraw Unstaked");
+            process_unstake(program_id, accounts, true)
+        }
+        StakeInstruction::MigratePool { bump } => {
+            msg!("DREGAN Staking: Migrate Pool");
+            process_migrate_pool(program_id, accounts, bump)
+        }
+        StakeInstruction::MigrateStake => {
+            msg!("DREGAN Staking: Migrate Stake Account");
+            process_migrate_stake(program_id, accounts)
+        }
+    }
+}
+
+/// Load the pool config, rejecting anything but the initialized pool PDA
+fn load_pool_config(
+    program_id: &Pubkey,
+    pool_config_account: &AccountInfo,
+) -> Result<PoolConfig, ProgramError> {
+    if pool_config_account.owner != program_id {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    let (expected_pool, _) = Pubkey::find_program_address(&[POOL_SEED], program_id);
+    if *pool_config_account.key != expected_pool {
+        msg!("Invalid pool config PDA");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    
+    let pool_config = PoolConfig::try_from_slice(&pool_config_account.data.borrow())?;
+    if !pool_config.is_initialized {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    Ok(pool_config)
+}
+
+/// Check the pool's vault authority PDA and return its bump
+fn vault_authority_bump(
+    program_id: &Pubkey,
+    pool_config_account: &AccountInfo,
+    vault_authority: &AccountInfo,
+) -> Result<u8, ProgramError> {
+    let (expected_authority, authority_bump) = Pubkey::find_program_address(
+        &[VAULT_SEED, pool_config_account.key.as_ref()],
+        program_id,
+    );
+    if *vault_authority.key != expected_authority {
+        msg!("Invalid vault authority");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(authority_bump)
+}
+
+fn create_pda_account<'a>(
+    program_id: &Pubkey,
+    payer: &AccountInfo<'a>,
+    account: &AccountInfo<'a>,
+    system_program_account: &AccountInfo<'a>,
+    space: usize,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    if *system_program_account.key != system_program::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    
+    if account.owner == program_id || account.data_len() > 0 {
+        msg!("Account {} already initialized", account.key);
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+    
+    let required = Rent::get()?.minimum_balance(space);
+    let accounts = [payer.clone(), account.clone(), system_program_account.clone()];
+    if account.lamports() == 0 {
+        return invoke_signed(
+            &system_instruction::create_account(payer.key, account.key, required, space as u64, program_id),
+            &accounts,
+            &[seeds],
+        );
+    }
+    
+    let top_up = required.saturating_sub(account.lamports());
+    if top_up > 0 {
+        invoke(&system_instruction::transfer(payer.key, account.key, top_up), &accounts)?;
     }
+    invoke_signed(&system_instruction::allocate(account.key, space as u64), &accounts, &[seeds])?;
+    invoke_signed(&system_instruction::assign(account.key, program_id), &accounts, &[seeds])
 }
 
 fn process_initialize_pool(
@@ -166,16 +490,48 @@ fn process_initialize_pool(
     let token_mint = next_account_info(accounts_iter)?;
     let stake_vault = next_account_info(accounts_iter)?;
     let reward_vault = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
     
     if !authority.is_signer {
         return Err(ProgramError::MissingRequiredSignature);
     }
     
-    // Verify pool_config is owned by this program
-    if pool_config.owner != program_id {
-        return Err(ProgramError::InvalidAccountOwner);
+    // There is exactly one pool: the PDA, created here and never again
+    let (expected_pool, expected_bump) = Pubkey::find_program_address(&[POOL_SEED], program_id);
+    if *pool_config.key != expected_pool || bump != expected_bump {
+        msg!("Invalid pool config PDA");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    // Vaults must already belong to this pool's vault authority
+    let (vault_authority, _) = Pubkey::find_program_address(
+        &[VAULT_SEED, pool_config.key.as_ref()],
+        program_id,
+    );
+    if stake_vault.key == reward_vault.key {
+        msg!("Stake and reward vaults must differ");
+        return Err(ProgramError::InvalidArgument);
+    }
+    for vault in [stake_vault, reward_vault] {
+        if vault.owner != &spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let vault_data = TokenAccount::unpack(&vault.data.borrow())?;
+        if vault_data.mint != *token_mint.key || vault_data.owner != vault_authority {
+            msg!("Vault {} is not a {} account owned by {}", vault.key, token_mint.key, vault_authority);
+            return Err(ProgramError::InvalidAccountData);
+        }
     }
     
+    // Creation fails once the pool exists, so the pool can never be re-initialized
+    create_pda_account(
+        program_id,
+        authority,
+        pool_config,
+        system_program_account,
+        PoolConfig::LEN,
+        &[POOL_SEED, &[bump]],
+    )?;
+    
     let config = PoolConfig {
         is_initialized: true,
         authority: *authority.key,
@@ -184,6 +540,16 @@ fn process_initialize_pool(
         reward_vault: *reward_vault.key,
         total_staked: 0,
         total_rewards_distributed: 0,
+        reward_liabilities: 0,
+        min_stake_amount: 1,
+        max_stake_amount: u64::MAX,
+        tier_caps: [u64::MAX; TIER_COUNT],
+        tier_staked: [0; TIER_COUNT],
+        campaign_start: 0,
+        deposit_end: i64::MAX,
+        campaign_end: i64::MAX,
+        unbonding_cooldowns: [0; TIER_COUNT],
+        unmigrated_staked: 0,
         bump,
     };
     
@@ -200,16 +566,12 @@ fn process_initialize_stake(
     let accounts_iter = &mut accounts.iter();
     let stake_account = next_account_info(accounts_iter)?;
     let owner = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
     
     if !owner.is_signer {
         return Err(ProgramError::MissingRequiredSignature);
     }
     
-    // Verify stake_account is owned by this program
-    if stake_account.owner != program_id {
-        return Err(ProgramError::InvalidAccountOwner);
-    }
-    
     // Verify PDA derivation
     let (expected_pda, expected_bump) = Pubkey::find_program_address(
         &[STAKE_SEED, owner.key.as_ref()],
@@ -220,6 +582,15 @@ fn process_initialize_stake(
         return Err(ProgramError::InvalidSeeds);
     }
     
+    create_pda_account(
+        program_id,
+        owner,
+        stake_account,
+        system_program_account,
+        StakeAccount::LEN,
+        &[STAKE_SEED, owner.key.as_ref(), &[bump]],
+    )?;
+    
     let stake_data = StakeAccount {
         is_initialized: true,
         owner: *owner.key,
@@ -228,6 +599,8 @@ fn process_initialize_stake(
         stake_timestamp: 0,
         unlock_timestamp: 0,
         claimed_rewards: 0,
+        accrual_end_timestamp: 0,
+        unbonding_until: 0,
         bump,
     };
     
@@ -254,8 +627,8 @@ fn process_stake(
         return Err(ProgramError::MissingRequiredSignature);
     }
     
-    // Verify accounts owned by program
-    if stake_account.owner != program_id || pool_config_account.owner != program_id {
+    // Verify stake account owned by program
+    if stake_account.owner != program_id {
         return Err(ProgramError::InvalidAccountOwner);
     }
     
@@ -265,16 +638,12 @@ fn process_stake(
     }
     
     let mut stake_data = StakeAccount::try_from_slice(&stake_account.data.borrow())?;
-    let mut pool_config = PoolConfig::try_from_slice(&pool_config_account.data.borrow())?;
+    let mut pool_config = load_pool_config(program_id, pool_config_account)?;
     
     if !stake_data.is_initialized {
         return Err(ProgramError::UninitializedAccount);
     }
     
-    if !pool_config.is_initialized {
-        return Err(ProgramError::UninitializedAccount);
-    }
-    
     if stake_data.owner != *owner.key {
         msg!("Stake account owner mismatch");
         return Err(ProgramError::InvalidAccountOwner);
@@ -286,12 +655,46 @@ fn process_stake(
         return Err(ProgramError::InvalidArgument);
     }
     
-    // Check if user already has an active stake
+    // Check if user already has an active stake (including one that is unbonding)
     if stake_data.amount > 0 {
         msg!("Already have active stake. Unstake first.");
         return Err(ProgramError::Custom(3));
     }
     
+    // Enforce campaign deposit window
+    let clock = Clock::get()?;
+    if clock.unix_timestamp < pool_config.campaign_start {
+        msg!("Campaign has not started. Opens at {}", pool_config.campaign_start);
+        return Err(ProgramError::Custom(13));
+    }
+    if clock.unix_timestamp > pool_config.deposit_end {
+        msg!("Deposit window closed at {}", pool_config.deposit_end);
+        return Err(ProgramError::Custom(14));
+    }
+    
+    // Enforce pool stake limits
+    if amount < pool_config.min_stake_amount {
+        msg!("Stake amount {} below minimum {}", amount, pool_config.min_stake_amount);
+        return Err(ProgramError::Custom(10));
+    }
+    if amount > pool_config.max_stake_amount {
+        msg!("Stake amount {} above maximum {}", amount, pool_config.max_stake_amount);
+        return Err(ProgramError::Custom(11));
+    }
+    let tier_index = tier.index();
+    let tier_staked = pool_config.tier_staked[tier_index]
+        .checked_add(amount)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    if tier_staked > pool_config.tier_caps[tier_index] {
+        msg!(
+            "Tier {:?} capacity exceeded: {} staked, cap {}",
+            tier,
+            pool_config.tier_staked[tier_index],
+            pool_config.tier_caps[tier_index]
+        );
+        return Err(ProgramError::Custom(12));
+    }
+    
     // Verify user has enough tokens
     let user_token_data = TokenAccount::unpack(&user_token_account.data.borrow())?;
     if user_token_data.amount < amount {
@@ -320,17 +723,26 @@ fn process_stake(
     )?;
     
     // Update stake account
-    let clock = Clock::get()?;
     stake_data.amount = amount;
     stake_data.tier = tier.clone();
     stake_data.stake_timestamp = clock.unix_timestamp;
     stake_data.unlock_timestamp = clock.unix_timestamp + tier.lock_duration();
     stake_data.claimed_rewards = 0;
+    // Locked tiers stop earning at unlock, Flexible after its accrual term
+    let accrual_end = match tier {
+        StakeTier::Flexible => clock.unix_timestamp.saturating_add(FLEXIBLE_ACCRUAL_TERM),
+        _ => stake_data.unlock_timestamp,
+    };
+    stake_data.accrual_end_timestamp = accrual_end.min(pool_config.campaign_end);
     
     // Update pool config
     pool_config.total_staked = pool_config.total_staked
         .checked_add(amount)
         .ok_or(ProgramError::ArithmeticOverflow)?;
+    pool_config.tier_staked[tier_index] = tier_staked;
+    pool_config.reward_liabilities = pool_config.reward_liabilities
+        .checked_add(stake_data.reserved_rewards())
+        .ok_or(ProgramError::ArithmeticOverflow)?;
     
     stake_data.serialize(&mut &mut stake_account.data.borrow_mut()[..])?;
     pool_config.serialize(&mut &mut pool_config_account.data.borrow_mut()[..])?;
@@ -339,9 +751,12 @@ fn process_stake(
     Ok(())
 }
 
+/// Shared by `Unstake` (instant exit for tiers without a cooldown) and
+/// `WithdrawUnstaked` (second phase after `RequestUnstake`).
 fn process_unstake(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
+    after_cooldown: bool,
 ) -> ProgramResult {
     let accounts_iter = &mut accounts.iter();
     let stake_account = next_account_info(accounts_iter)?;
@@ -356,13 +771,18 @@ fn process_unstake(
         return Err(ProgramError::MissingRequiredSignature);
     }
     
-    // Verify accounts owned by program
-    if stake_account.owner != program_id || pool_config_account.owner != program_id {
+    // Verify stake account owned by program
+    if stake_account.owner != program_id {
         return Err(ProgramError::InvalidAccountOwner);
     }
     
+    // Verify token program; it is handed the vault authority's signature
+    if *token_program.key != spl_token::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    
     let mut stake_data = StakeAccount::try_from_slice(&stake_account.data.borrow())?;
-    let mut pool_config = PoolConfig::try_from_slice(&pool_config_account.data.borrow())?;
+    let mut pool_config = load_pool_config(program_id, pool_config_account)?;
     
     if !stake_data.is_initialized {
         return Err(ProgramError::UninitializedAccount);
@@ -377,24 +797,44 @@ fn process_unstake(
         return Err(ProgramError::Custom(4));
     }
     
-    // Check lock period
+    if *stake_vault.key != pool_config.stake_vault {
+        msg!("Invalid stake vault");
+        return Err(ProgramError::InvalidArgument);
+    }
+    
+    // Once the campaign is over every position can leave without a cooldown
     let clock = Clock::get()?;
-    if clock.unix_timestamp < stake_data.unlock_timestamp {
-        msg!("Cannot unstake: lock period not ended. Unlock at {}", stake_data.unlock_timestamp);
-        return Err(ProgramError::Custom(1));
+    let campaign_over = clock.unix_timestamp >= pool_config.campaign_end;
+    if after_cooldown {
+        if stake_data.unbonding_until == 0 {
+            msg!("No unstake requested");
+            return Err(ProgramError::Custom(16));
+        }
+        if clock.unix_timestamp < stake_data.unbonding_until && !campaign_over {
+            msg!("Unbonding cooldown active. Withdrawable at {}", stake_data.unbonding_until);
+            return Err(ProgramError::Custom(17));
+        }
+    } else {
+        if stake_data.unbonding_until != 0 {
+            msg!("Unstake already requested. Withdraw at {}", stake_data.unbonding_until);
+            return Err(ProgramError::Custom(15));
+        }
+        
+        // Check lock period; once the campaign is over every position is free to leave
+        if !stake_data.lock_expired(clock.unix_timestamp, pool_config.campaign_end) {
+            msg!("Cannot unstake: lock period not ended. Unlock at {}", stake_data.unlock_timestamp);
+            return Err(ProgramError::Custom(1));
+        }
+        
+        if pool_config.unbonding_cooldowns[stake_data.tier.index()] > 0 && !campaign_over {
+            msg!("Tier {:?} has an unbonding cooldown. Use RequestUnstake", stake_data.tier);
+            return Err(ProgramError::Custom(18));
+        }
     }
     
     let amount = stake_data.amount;
     
-    // Derive vault authority PDA
-    let (expected_authority, authority_bump) = Pubkey::find_program_address(
-        &[VAULT_SEED],
-        program_id,
-    );
-    if *vault_authority.key != expected_authority {
-        msg!("Invalid vault authority");
-        return Err(ProgramError::InvalidSeeds);
-    }
+    let authority_bump = vault_authority_bump(program_id, pool_config_account, vault_authority)?;
     
     // Transfer tokens from vault back to user
     let transfer_ix = spl_token::instruction::transfer(
@@ -406,7 +846,7 @@ fn process_unstake(
         amount,
     )?;
     
-    let seeds = &[VAULT_SEED, &[authority_bump]];
+    let seeds = &[VAULT_SEED, pool_config_account.key.as_ref(), &[authority_bump]];
     invoke_signed(
         &transfer_ix,
         &[
@@ -418,13 +858,26 @@ fn process_unstake(
         &[seeds],
     )?;
     
+    // Release whatever reward reservation the position still holds
+    let released = stake_data.reserved_rewards();
+    
     // Update stake account
     stake_data.amount = 0;
     stake_data.stake_timestamp = 0;
     stake_data.unlock_timestamp = 0;
+    stake_data.accrual_end_timestamp = 0;
+    stake_data.unbonding_until = 0;
     
     // Update pool config
-    pool_config.total_staked = pool_config.total_staked.saturating_sub(amount);
+    pool_config.total_staked = pool_config.total_staked
+        .checked_sub(amount)
+        .ok_or_else(|| {
+            msg!("Pool total_staked {} below position amount {}", pool_config.total_staked, amount);
+            ProgramError::ArithmeticOverflow
+        })?;
+    let tier_index = stake_data.tier.index();
+    pool_config.tier_staked[tier_index] = pool_config.tier_staked[tier_index].saturating_sub(amount);
+    pool_config.reward_liabilities = pool_config.reward_liabilities.saturating_sub(released);
     
     stake_data.serialize(&mut &mut stake_account.data.borrow_mut()[..])?;
     pool_config.serialize(&mut &mut pool_config_account.data.borrow_mut()[..])?;
@@ -450,13 +903,18 @@ fn process_claim_rewards(
         return Err(ProgramError::MissingRequiredSignature);
     }
     
-    // Verify accounts owned by program
-    if stake_account.owner != program_id || pool_config_account.owner != program_id {
+    // Verify stake account owned by program
+    if stake_account.owner != program_id {
         return Err(ProgramError::InvalidAccountOwner);
     }
     
+    // Verify token program; it is handed the vault authority's signature
+    if *token_program.key != spl_token::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    
     let mut stake_data = StakeAccount::try_from_slice(&stake_account.data.borrow())?;
-    let mut pool_config = PoolConfig::try_from_slice(&pool_config_account.data.borrow())?;
+    let mut pool_config = load_pool_config(program_id, pool_config_account)?;
     
     if !stake_data.is_initialized {
         return Err(ProgramError::UninitializedAccount);
@@ -471,8 +929,16 @@ fn process_claim_rewards(
         return Err(ProgramError::Custom(5));
     }
     
-    // Calculate claimable rewards
+    if *reward_vault.key != pool_config.reward_vault {
+        msg!("Invalid reward vault");
+        return Err(ProgramError::InvalidArgument);
+    }
+    
+    // A campaign shortened after staking also ends this position's accrual
     let clock = Clock::get()?;
+    let clipped = stake_data.clip_accrual_end(pool_config.campaign_end);
+    
+    // Calculate claimable rewards
     let total_rewards = stake_data.calculate_rewards(clock.unix_timestamp);
     let claimable = total_rewards.saturating_sub(stake_data.claimed_rewards);
     
@@ -488,15 +954,7 @@ fn process_claim_rewards(
         return Err(ProgramError::InsufficientFunds);
     }
     
-    // Derive vault authority PDA
-    let (expected_authority, authority_bump) = Pubkey::find_program_address(
-        &[VAULT_SEED],
-        program_id,
-    );
-    if *vault_authority.key != expected_authority {
-        msg!("Invalid vault authority");
-        return Err(ProgramError::InvalidSeeds);
-    }
+    let authority_bump = vault_authority_bump(program_id, pool_config_account, vault_authority)?;
     
     // Transfer rewards from reward vault to user
     let transfer_ix = spl_token::instruction::transfer(
@@ -508,7 +966,7 @@ fn process_claim_rewards(
         claimable,
     )?;
     
-    let seeds = &[VAULT_SEED, &[authority_bump]];
+    let seeds = &[VAULT_SEED, pool_config_account.key.as_ref(), &[authority_bump]];
     invoke_signed(
         &transfer_ix,
         &[
@@ -521,12 +979,15 @@ fn process_claim_rewards(
     )?;
     
     // Update stake account
+    let reserved_before = stake_data.reserved_rewards();
     stake_data.claimed_rewards = total_rewards;
+    let released = clipped + reserved_before.saturating_sub(stake_data.reserved_rewards());
     
     // Update pool config
     pool_config.total_rewards_distributed = pool_config.total_rewards_distributed
         .checked_add(claimable)
         .ok_or(ProgramError::ArithmeticOverflow)?;
+    pool_config.reward_liabilities = pool_config.reward_liabilities.saturating_sub(released);
     
     stake_data.serialize(&mut &mut stake_account.data.borrow_mut()[..])?;
     pool_config.serialize(&mut &mut pool_config_account.data.borrow_mut()[..])?;
@@ -534,3 +995,781 @@ fn process_claim_rewards(
     msg!("Claimed {} reward tokens", claimable);
     Ok(())
 }
+
+fn process_withdraw_surplus_rewards(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let pool_config_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let reward_vault = next_account_info(accounts_iter)?;
+    let destination_token_account = next_account_info(accounts_iter)?;
+    let vault_authority = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+    
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    // Verify token program
+    if *token_program.key != spl_token::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    
+    let pool_config = load_pool_config(program_id, pool_config_account)?;
+    
+    if pool_config.authority != *authority.key {
+        msg!("Pool authority mismatch");
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    // Verify reward vault matches pool config
+    if *reward_vault.key != pool_config.reward_vault {
+        msg!("Invalid reward vault");
+        return Err(ProgramError::InvalidArgument);
+    }
+    
+    // Unmigrated legacy positions are owed rewards that are not reserved yet
+    if pool_config.unmigrated_staked > 0 {
+        msg!("{} staked tokens still await MigrateStake", pool_config.unmigrated_staked);
+        return Err(ProgramError::Custom(19));
+    }
+    
+    // Only the part of the vault not owed to open positions can leave
+    let reward_vault_data = TokenAccount::unpack(&reward_vault.data.borrow())?;
+    let surplus = reward_vault_data.amount.saturating_sub(pool_config.reward_liabilities);
+    if amount == 0 || amount > surplus {
+        msg!("Requested {} exceeds surplus rewards {}", amount, surplus);
+        return Err(ProgramError::Custom(6));
+    }
+    
+    let authority_bump = vault_authority_bump(program_id, pool_config_account, vault_authority)?;
+    
+    // Transfer surplus from reward vault to destination
+    let transfer_ix = spl_token::instruction::transfer(
+        token_program.key,
+        reward_vault.key,
+        destination_token_account.key,
+        vault_authority.key,
+        &[],
+        amount,
+    )?;
+    
+    let seeds = &[VAULT_SEED, pool_config_account.key.as_ref(), &[authority_bump]];
+    invoke_signed(
+        &transfer_ix,
+        &[
+            reward_vault.clone(),
+            destination_token_account.clone(),
+            vault_authority.clone(),
+            token_program.clone(),
+        ],
+        &[seeds],
+    )?;
+    
+    StakeEvent::SurplusRewardsWithdrawn {
+        authority: *authority.key,
+        destination: *destination_token_account.key,
+        amount,
+        remaining_surplus: surplus - amount,
+    }
+    .emit()?;
+    
+    msg!("Withdrew {} surplus reward tokens", amount);
+    Ok(())
+}
+
+fn process_sweep_excess(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let pool_config_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let stake_vault = next_account_info(accounts_iter)?;
+    let destination_token_account = next_account_info(accounts_iter)?;
+    let vault_authority = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+    
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    // Verify token program
+    if *token_program.key != spl_token::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    
+    let pool_config = load_pool_config(program_id, pool_config_account)?;
+    
+    if pool_config.authority != *authority.key {
+        msg!("Pool authority mismatch");
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    // Verify stake vault matches pool config
+    if *stake_vault.key != pool_config.stake_vault {
+        msg!("Invalid stake vault");
+        return Err(ProgramError::InvalidArgument);
+    }
+    
+    // Anything above total_staked was never staked through the program
+    let stake_vault_data = TokenAccount::unpack(&stake_vault.data.borrow())?;
+    let excess = stake_vault_data.amount.saturating_sub(pool_config.total_staked);
+    if excess == 0 {
+        msg!("No excess tokens in stake vault");
+        return Err(ProgramError::Custom(7));
+    }
+    
+    let authority_bump = vault_authority_bump(program_id, pool_config_account, vault_authority)?;
+    
+    // Transfer excess from stake vault to destination
+    let transfer_ix = spl_token::instruction::transfer(
+        token_program.key,
+        stake_vault.key,
+        destination_token_account.key,
+        vault_authority.key,
+        &[],
+        excess,
+    )?;
+    
+    let seeds = &[VAULT_SEED, pool_config_account.key.as_ref(), &[authority_bump]];
+    invoke_signed(
+        &transfer_ix,
+        &[
+            stake_vault.clone(),
+            destination_token_account.clone(),
+            vault_authority.clone(),
+            token_program.clone(),
+        ],
+        &[seeds],
+    )?;
+    
+    StakeEvent::ExcessSwept {
+        authority: *authority.key,
+        destination: *destination_token_account.key,
+        amount: excess,
+    }
+    .emit()?;
+    
+    msg!("Swept {} excess tokens from stake vault", excess);
+    Ok(())
+}
+
+fn process_audit_pool(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let pool_config_account = next_account_info(accounts_iter)?;
+    let stake_vault = next_account_info(accounts_iter)?;
+    let reward_vault = next_account_info(accounts_iter)?;
+    
+    let pool_config = load_pool_config(program_id, pool_config_account)?;
+    
+    // Verify vaults match pool config
+    if *stake_vault.key != pool_config.stake_vault {
+        msg!("Invalid stake vault");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if *reward_vault.key != pool_config.reward_vault {
+        msg!("Invalid reward vault");
+        return Err(ProgramError::InvalidArgument);
+    }
+    
+    let stake_vault_data = TokenAccount::unpack(&stake_vault.data.borrow())?;
+    let reward_vault_data = TokenAccount::unpack(&reward_vault.data.borrow())?;
+    
+    if stake_vault_data.amount < pool_config.total_staked {
+        msg!(
+            "AUDIT FAILED: stake vault holds {} but total_staked is {}",
+            stake_vault_data.amount,
+            pool_config.total_staked
+        );
+        return Err(ProgramError::Custom(8));
+    }
+    
+    if reward_vault_data.amount < pool_config.reward_liabilities {
+        msg!(
+            "AUDIT FAILED: reward vault holds {} but liabilities are {}",
+            reward_vault_data.amount,
+            pool_config.reward_liabilities
+        );
+        return Err(ProgramError::Custom(9));
+    }
+    
+    StakeEvent::PoolAudited {
+        stake_vault_balance: stake_vault_data.amount,
+        total_staked: pool_config.total_staked,
+        reward_vault_balance: reward_vault_data.amount,
+        reward_liabilities: pool_config.reward_liabilities,
+    }
+    .emit()?;
+    
+    msg!("Pool audit passed");
+    Ok(())
+}
+
+fn process_set_stake_limits(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    min_stake_amount: u64,
+    max_stake_amount: u64,
+    tier_caps: [u64; TIER_COUNT],
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let pool_config_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    let mut pool_config = load_pool_config(program_id, pool_config_account)?;
+    
+    if pool_config.authority != *authority.key {
+        msg!("Pool authority mismatch");
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    // A zero minimum would let empty positions through again
+    if min_stake_amount == 0 || min_stake_amount > max_stake_amount {
+        msg!("Invalid stake bounds: min {}, max {}", min_stake_amount, max_stake_amount);
+        return Err(ProgramError::InvalidArgument);
+    }
+    
+    // Caps only bound new stakes; existing positions above a lowered cap stay put
+    pool_config.min_stake_amount = min_stake_amount;
+    pool_config.max_stake_amount = max_stake_amount;
+    pool_config.tier_caps = tier_caps;
+    
+    pool_config.serialize(&mut &mut pool_config_account.data.borrow_mut()[..])?;
+    
+    StakeEvent::StakeLimitsUpdated {
+        authority: *authority.key,
+        min_stake_amount,
+        max_stake_amount,
+        tier_caps,
+    }
+    .emit()?;
+    
+    msg!(
+        "Stake limits set: min {}, max {}, tier caps {:?}",
+        min_stake_amount,
+        max_stake_amount,
+        tier_caps
+    );
+    Ok(())
+}
+
+fn process_set_campaign(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    campaign_start: i64,
+    deposit_end: i64,
+    campaign_end: i64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let pool_config_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    let mut pool_config = load_pool_config(program_id, pool_config_account)?;
+    
+    if pool_config.authority != *authority.key {
+        msg!("Pool authority mismatch");
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    if campaign_start > deposit_end || deposit_end > campaign_end {
+        msg!(
+            "Invalid campaign window: start {}, deposit end {}, end {}",
+            campaign_start,
+            deposit_end,
+            campaign_end
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+    
+    // Open positions stop accruing at a sooner campaign_end (applied when they next
+    // claim or request unstake); a later one does not extend them
+    pool_config.campaign_start = campaign_start;
+    pool_config.deposit_end = deposit_end;
+    pool_config.campaign_end = campaign_end;
+    
+    pool_config.serialize(&mut &mut pool_config_account.data.borrow_mut()[..])?;
+    
+    StakeEvent::CampaignUpdated {
+        authority: *authority.key,
+        campaign_start,
+        deposit_end,
+        campaign_end,
+    }
+    .emit()?;
+    
+    msg!(
+        "Campaign set: start {}, deposit end {}, end {}",
+        campaign_start,
+        deposit_end,
+        campaign_end
+    );
+    Ok(())
+}
+
+fn process_set_unbonding_cooldowns(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    unbonding_cooldowns: [i64; TIER_COUNT],
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let pool_config_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    let mut pool_config = load_pool_config(program_id, pool_config_account)?;
+    
+    if pool_config.authority != *authority.key {
+        msg!("Pool authority mismatch");
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    if unbonding_cooldowns.iter().any(|cooldown| *cooldown < 0) {
+        msg!("Unbonding cooldowns must not be negative");
+        return Err(ProgramError::InvalidArgument);
+    }
+    
+    // Positions already unbonding keep the withdrawal time they were given
+    pool_config.unbonding_cooldowns = unbonding_cooldowns;
+    
+    pool_config.serialize(&mut &mut pool_config_account.data.borrow_mut()[..])?;
+    
+    StakeEvent::UnbondingCooldownsUpdated {
+        authority: *authority.key,
+        unbonding_cooldowns,
+    }
+    .emit()?;
+    
+    msg!("Unbonding cooldowns set: {:?}", unbonding_cooldowns);
+    Ok(())
+}
+
+fn process_request_unstake(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let stake_account = next_account_info(accounts_iter)?;
+    let owner = next_account_info(accounts_iter)?;
+    let pool_config_account = next_account_info(accounts_iter)?;
+    
+    if !owner.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    // Verify stake account owned by program
+    if stake_account.owner != program_id {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    let mut stake_data = StakeAccount::try_from_slice(&stake_account.data.borrow())?;
+    let mut pool_config = load_pool_config(program_id, pool_config_account)?;
+    
+    if !stake_data.is_initialized {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    
+    if stake_data.owner != *owner.key {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    if stake_data.amount == 0 {
+        msg!("No tokens staked");
+        return Err(ProgramError::Custom(4));
+    }
+    
+    if stake_data.unbonding_until != 0 {
+        msg!("Unstake already requested. Withdraw at {}", stake_data.unbonding_until);
+        return Err(ProgramError::Custom(15));
+    }
+    
+    let clock = Clock::get()?;
+    if !stake_data.lock_expired(clock.unix_timestamp, pool_config.campaign_end) {
+        msg!("Cannot unstake: lock period not ended. Unlock at {}", stake_data.unlock_timestamp);
+        return Err(ProgramError::Custom(1));
+    }
+    
+    let cooldown = pool_config.unbonding_cooldowns[stake_data.tier.index()];
+    let withdrawable_at = clock.unix_timestamp
+        .checked_add(cooldown)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    
+    // Stop accrual now (or at a campaign end already passed); release what the cut-off frees up
+    let released = stake_data.clip_accrual_end(clock.unix_timestamp.min(pool_config.campaign_end));
+    stake_data.unbonding_until = withdrawable_at;
+    
+    pool_config.reward_liabilities = pool_config.reward_liabilities.saturating_sub(released);
+    
+    stake_data.serialize(&mut &mut stake_account.data.borrow_mut()[..])?;
+    pool_config.serialize(&mut &mut pool_config_account.data.borrow_mut()[..])?;
+    
+    StakeEvent::UnstakeRequested {
+        owner: *owner.key,
+        amount: stake_data.amount,
+        withdrawable_at,
+    }
+    .emit()?;
+    
+    msg!("Unstake requested for {} tokens, withdrawable at {}", stake_data.amount, withdrawable_at);
+    Ok(())
+}
+
+/// Require the program's upgrade authority to sign, for one-off migrations
+fn check_upgrade_authority(
+    program_id: &Pubkey,
+    program_data: &AccountInfo,
+    upgrade_authority: &AccountInfo,
+) -> ProgramResult {
+    let (expected_program_data, _) = Pubkey::find_program_address(
+        &[program_id.as_ref()],
+        &bpf_loader_upgradeable::id(),
+    );
+    if *program_data.key != expected_program_data || *program_data.owner != bpf_loader_upgradeable::id() {
+        msg!("Invalid program data account");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    
+    let metadata_len = UpgradeableLoaderState::size_of_programdata_metadata();
+    let data = program_data.data.borrow();
+    if data.len() < metadata_len {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let state: UpgradeableLoaderState = limited_deserialize(&data[..metadata_len], metadata_len as u64)
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+    match state {
+        UpgradeableLoaderState::ProgramData { upgrade_authority_address: Some(authority), .. }
+            if authority == *upgrade_authority.key && upgrade_authority.is_signer => Ok(()),
+        _ => {
+            msg!("Only the program upgrade authority can migrate the pool");
+            Err(ProgramError::MissingRequiredSignature)
+        }
+    }
+}
+
+fn process_migrate_pool(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    bump: u8,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let legacy_pool = next_account_info(accounts_iter)?;
+    let pool_config_account = next_account_info(accounts_iter)?;
+    let upgrade_authority = next_account_info(accounts_iter)?;
+    let program_data = next_account_info(accounts_iter)?;
+    let stake_vault = next_account_info(accounts_iter)?;
+    let reward_vault = next_account_info(accounts_iter)?;
+    let legacy_vault_authority = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+    
+    check_upgrade_authority(program_id, program_data, upgrade_authority)?;
+    
+    if *token_program.key != spl_token::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    
+    if legacy_pool.owner != program_id || legacy_pool.data_len() != LegacyPoolConfig::LEN {
+        msg!("Not a legacy pool config");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let legacy = LegacyPoolConfig::try_from_slice(&legacy_pool.data.borrow())?;
+    if !legacy.is_initialized {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    
+    if *stake_vault.key != legacy.stake_vault || *reward_vault.key != legacy.reward_vault {
+        msg!("Vaults do not match the legacy pool");
+        return Err(ProgramError::InvalidArgument);
+    }
+    
+    let (expected_pool, expected_bump) = Pubkey::find_program_address(&[POOL_SEED], program_id);
+    if *pool_config_account.key != expected_pool || bump != expected_bump {
+        msg!("Invalid pool config PDA");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    
+    let (expected_legacy_authority, legacy_authority_bump) =
+        Pubkey::find_program_address(&[VAULT_SEED], program_id);
+    if *legacy_vault_authority.key != expected_legacy_authority {
+        msg!("Invalid legacy vault authority");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    
+    create_pda_account(
+        program_id,
+        upgrade_authority,
+        pool_config_account,
+        system_program_account,
+        PoolConfig::LEN,
+        &[POOL_SEED, &[bump]],
+    )?;
+    
+    // Hand both vaults from the legacy [VAULT_SEED] authority to the pool's vault authority
+    let (vault_authority, _) = Pubkey::find_program_address(
+        &[VAULT_SEED, pool_config_account.key.as_ref()],
+        program_id,
+    );
+    for vault in [stake_vault, reward_vault] {
+        invoke_signed(
+            &spl_token::instruction::set_authority(
+                token_program.key,
+                vault.key,
+                Some(&vault_authority),
+                spl_token::instruction::AuthorityType::AccountOwner,
+                legacy_vault_authority.key,
+                &[],
+            )?,
+            &[vault.clone(), legacy_vault_authority.clone(), token_program.clone()],
+            &[&[VAULT_SEED, &[legacy_authority_bump]]],
+        )?;
+    }
+    
+    // Liabilities start at zero and grow as MigrateStake reserves each position;
+    // surplus withdrawals wait until every legacy position is migrated
+    let pool_config = PoolConfig {
+        is_initialized: true,
+        authority: legacy.authority,
+        token_mint: legacy.token_mint,
+        stake_vault: legacy.stake_vault,
+        reward_vault: legacy.reward_vault,
+        total_staked: legacy.total_staked,
+        total_rewards_distributed: legacy.total_rewards_distributed,
+        reward_liabilities: 0,
+        min_stake_amount: 1,
+        max_stake_amount: u64::MAX,
+        tier_caps: [u64::MAX; TIER_COUNT],
+        tier_staked: [0; TIER_COUNT],
+        campaign_start: 0,
+        deposit_end: i64::MAX,
+        campaign_end: i64::MAX,
+        unbonding_cooldowns: [0; TIER_COUNT],
+        unmigrated_staked: legacy.total_staked,
+        bump,
+    };
+    pool_config.serialize(&mut &mut pool_config_account.data.borrow_mut()[..])?;
+    
+    // Close the legacy pool so it can never be read again
+    let legacy_lamports = legacy_pool.lamports();
+    **upgrade_authority.lamports.borrow_mut() = upgrade_authority
+        .lamports()
+        .checked_add(legacy_lamports)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    **legacy_pool.lamports.borrow_mut() = 0;
+    legacy_pool.data.borrow_mut().fill(0);
+    
+    StakeEvent::PoolMigrated {
+        legacy_pool: *legacy_pool.key,
+        pool_config: *pool_config_account.key,
+        total_staked: pool_config.total_staked,
+    }
+    .emit()?;
+    
+    msg!("Legacy pool {} migrated to {}", legacy_pool.key, pool_config_account.key);
+    Ok(())
+}
+
+fn process_migrate_stake(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let stake_account = next_account_info(accounts_iter)?;
+    let pool_config_account = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+    
+    if !payer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    if *system_program_account.key != system_program::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    
+    if stake_account.owner != program_id {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    
+    if stake_account.data_len() != LegacyStakeAccount::LEN {
+        msg!("Stake account is not in the legacy layout");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let legacy = LegacyStakeAccount::try_from_slice(&stake_account.data.borrow())?;
+    if !legacy.is_initialized {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    
+    let (expected_pda, _) = Pubkey::find_program_address(
+        &[STAKE_SEED, legacy.owner.as_ref()],
+        program_id,
+    );
+    if *stake_account.key != expected_pda {
+        msg!("Invalid stake account PDA");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    
+    let mut pool_config = load_pool_config(program_id, pool_config_account)?;
+    
+    let top_up = Rent::get()?
+        .minimum_balance(StakeAccount::LEN)
+        .saturating_sub(stake_account.lamports());
+    if top_up > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, stake_account.key, top_up),
+            &[payer.clone(), stake_account.clone(), system_program_account.clone()],
+        )?;
+    }
+    stake_account.realloc(StakeAccount::LEN, true)?;
+    
+    // Legacy positions accrued without end; keep what is earned so far and
+    // stop at unlock from here on, like a position staked today
+    let clock = Clock::get()?;
+    let accrual_end_timestamp = if legacy.amount > 0 {
+        legacy.unlock_timestamp.max(clock.unix_timestamp).min(pool_config.campaign_end)
+    } else {
+        0
+    };
+    let stake_data = StakeAccount {
+        is_initialized: true,
+        owner: legacy.owner,
+        amount: legacy.amount,
+        tier: legacy.tier,
+        stake_timestamp: legacy.stake_timestamp,
+        unlock_timestamp: legacy.unlock_timestamp,
+        claimed_rewards: legacy.claimed_rewards,
+        accrual_end_timestamp,
+        unbonding_until: 0,
+        bump: legacy.bump,
+    };
+    
+    let reserved_rewards = stake_data.reserved_rewards();
+    let tier_index = stake_data.tier.index();
+    pool_config.tier_staked[tier_index] = pool_config.tier_staked[tier_index]
+        .checked_add(stake_data.amount)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    pool_config.reward_liabilities = pool_config.reward_liabilities
+        .checked_add(reserved_rewards)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    pool_config.unmigrated_staked = pool_config.unmigrated_staked.saturating_sub(stake_data.amount);
+    
+    stake_data.serialize(&mut &mut stake_account.data.borrow_mut()[..])?;
+    pool_config.serialize(&mut &mut pool_config_account.data.borrow_mut()[..])?;
+    
+    StakeEvent::StakeMigrated {
+        owner: stake_data.owner,
+        amount: stake_data.amount,
+        reserved_rewards,
+    }
+    .emit()?;
+    
+    msg!("Stake account migrated for {}", stake_data.owner);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    const DAY: i64 = 24 * 60 * 60;
+    const START: i64 = 1_700_000_000;
+    
+    fn position(amount: u64, tier: StakeTier, accrual_end_timestamp: i64) -> StakeAccount {
+        StakeAccount {
+            is_initialized: true,
+            owner: Pubkey::new_unique(),
+            amount,
+            unlock_timestamp: START + tier.lock_duration(),
+            tier,
+            stake_timestamp: START,
+            claimed_rewards: 0,
+            accrual_end_timestamp,
+            unbonding_until: 0,
+            bump: 255,
+        }
+    }
+    
+    #[test]
+    fn rewards_stop_at_accrual_end() {
+        let stake = position(1_000_000, StakeTier::Basic, START + 30 * DAY);
+        // 1_000_000 * 10% * 30 / 365
+        assert_eq!(stake.calculate_rewards(START + 30 * DAY), 8_219);
+        assert_eq!(stake.calculate_rewards(START + 365 * DAY), 8_219);
+        assert_eq!(stake.calculate_rewards(START + 15 * DAY), 4_109);
+        assert_eq!(stake.calculate_rewards(START - DAY), 0);
+    }
+    
+    #[test]
+    fn rewards_do_not_overflow() {
+        let mut stake = position(u64::MAX, StakeTier::Elite, i64::MAX);
+        stake.stake_timestamp = 1;
+        assert_eq!(stake.calculate_rewards(i64::MAX), u64::MAX);
+        let stake = position(u64::MAX, StakeTier::Elite, START + 90 * DAY);
+        assert_eq!(stake.calculate_rewards(START + 90 * DAY), (u64::MAX as u128 * 2000 * 90 / (365 * 10000)) as u64);
+    }
+    
+    #[test]
+    fn reserved_rewards_cover_every_future_claim() {
+        let mut stake = position(5_000_000, StakeTier::Pro, START + 60 * DAY);
+        let full = stake.calculate_rewards(START + 60 * DAY);
+        assert_eq!(stake.reserved_rewards(), full);
+        
+        // Reserved shrinks by exactly what is claimed, and never below zero
+        stake.claimed_rewards = stake.calculate_rewards(START + 20 * DAY);
+        assert_eq!(stake.reserved_rewards(), full - stake.claimed_rewards);
+        for now in [START + 20 * DAY, START + 60 * DAY, START + 1_000 * DAY] {
+            assert!(stake.calculate_rewards(now) - stake.claimed_rewards <= stake.reserved_rewards());
+        }
+        stake.claimed_rewards = full + 1;
+        assert_eq!(stake.reserved_rewards(), 0);
+    }
+    
+    #[test]
+    fn reserved_rewards_empty_position() {
+        let stake = position(0, StakeTier::Elite, START + 90 * DAY);
+        assert_eq!(stake.reserved_rewards(), 0);
+        let mut stake = position(1_000, StakeTier::Elite, START + 90 * DAY);
+        stake.stake_timestamp = 0;
+        assert_eq!(stake.reserved_rewards(), 0);
+    }
+    
+    #[test]
+    fn clip_accrual_end_releases_unearned_reservation() {
+        let mut stake = position(1_000_000, StakeTier::Basic, START + 30 * DAY);
+        let before = stake.reserved_rewards();
+        let released = stake.clip_accrual_end(START + 15 * DAY);
+        assert_eq!(stake.accrual_end_timestamp, START + 15 * DAY);
+        assert_eq!(released, before - stake.reserved_rewards());
+        assert_eq!(stake.reserved_rewards(), 4_109);
+        
+        // A later end never extends accrual or releases anything
+        assert_eq!(stake.clip_accrual_end(START + 30 * DAY), 0);
+        assert_eq!(stake.accrual_end_timestamp, START + 15 * DAY);
+    }
+    
+    #[test]
+    fn lock_expires_at_unlock_or_campaign_end() {
+        let stake = position(1_000, StakeTier::Elite, START + 90 * DAY);
+        assert!(!stake.lock_expired(START + 89 * DAY, i64::MAX));
+        assert!(stake.lock_expired(START + 90 * DAY, i64::MAX));
+        assert!(stake.lock_expired(START + DAY, START + DAY));
+        let flexible = position(1_000, StakeTier::Flexible, START + FLEXIBLE_ACCRUAL_TERM);
+        assert!(flexible.lock_expired(START, i64::MAX));
+    }
+}
@@ -0,0 +1,179 @@
+use borsh::BorshDeserialize;
+use dregan_staking::{LegacyStakeAccount, PoolConfig, StakeAccount, POOL_SEED};
+use solana_client::{
+    rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::RpcFilterType,
+};
+use solana_sdk::{
+    account::Account, commitment_config::CommitmentConfig, program_pack::Pack, pubkey::Pubkey,
+};
+use spl_token::state::Account as TokenAccount;
+use std::{process, str::FromStr};
+
+// DREGAN Pool Auditor - Off-chain invariant check for dregan-staking
+// Sums every StakeAccount via getProgramAccounts and compares against PoolConfig
+// at the program's [b"pool"] PDA, counting legacy positions still awaiting MigrateStake
+
+const DEFAULT_RPC_URL: &str = "https://api.devnet.solana.com";
+
+fn usage() -> ! {
+    eprintln!("Usage: dregan-pool-auditor [rpc_url] [program_id]");
+    process::exit(2);
+}
+
+fn parse_pubkey(value: &str) -> Pubkey {
+    Pubkey::from_str(value).unwrap_or_else(|_| {
+        eprintln!("Invalid pubkey: {}", value);
+        usage();
+    })
+}
+
+fn fetch_token_amount(client: &RpcClient, address: &Pubkey) -> Result<u64, String> {
+    let data = client
+        .get_account_data(address)
+        .map_err(|e| format!("failed to fetch token account {}: {}", address, e))?;
+    let token = TokenAccount::unpack(&data)
+        .map_err(|e| format!("failed to unpack token account {}: {}", address, e))?;
+    Ok(token.amount)
+}
+
+/// Every program account allocated at exactly `len` bytes
+fn fetch_accounts_of_len(
+    client: &RpcClient,
+    program_id: &Pubkey,
+    len: usize,
+) -> Result<Vec<(Pubkey, Account)>, String> {
+    client
+        .get_program_accounts_with_config(
+            program_id,
+            RpcProgramAccountsConfig {
+                filters: Some(vec![RpcFilterType::DataSize(len as u64)]),
+                account_config: RpcAccountInfoConfig {
+                    commitment: Some(CommitmentConfig::confirmed()),
+                    ..RpcAccountInfoConfig::default()
+                },
+                ..RpcProgramAccountsConfig::default()
+            },
+        )
+        .map_err(|e| format!("getProgramAccounts failed: {}", e))
+}
+
+fn run() -> Result<bool, String> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() > 2 {
+        usage();
+    }
+    let rpc_url = args.first().map(String::as_str).unwrap_or(DEFAULT_RPC_URL);
+    let program_id = args
+        .get(1)
+        .map(|value| parse_pubkey(value))
+        .unwrap_or_else(dregan_staking::id);
+    let (pool_config_key, _) = Pubkey::find_program_address(&[POOL_SEED], &program_id);
+
+    let client = RpcClient::new_with_commitment(rpc_url.to_string(), CommitmentConfig::confirmed());
+
+    let pool_data = client
+        .get_account_data(&pool_config_key)
+        .map_err(|e| format!("failed to fetch pool config: {}", e))?;
+    let pool_config = PoolConfig::try_from_slice(&pool_data)
+        .map_err(|e| format!("failed to decode pool config: {}", e))?;
+
+    // Every StakeAccount is allocated at exactly StakeAccount::LEN bytes; positions
+    // not yet grown by MigrateStake keep the legacy length
+    let stake_accounts = fetch_accounts_of_len(&client, &program_id, StakeAccount::LEN)?;
+    let legacy_accounts = fetch_accounts_of_len(&client, &program_id, LegacyStakeAccount::LEN)?;
+
+    let mut positions = 0u64;
+    let mut summed_staked: u128 = 0;
+    let mut summed_reserved: u128 = 0;
+    for (address, account) in &stake_accounts {
+        let stake = match StakeAccount::try_from_slice(&account.data) {
+            Ok(stake) if stake.is_initialized => stake,
+            Ok(_) => continue,
+            Err(e) => {
+                eprintln!("Skipping undecodable account {}: {}", address, e);
+                continue;
+            }
+        };
+        if stake.amount > 0 {
+            positions += 1;
+        }
+        summed_staked += stake.amount as u128;
+        summed_reserved += stake.reserved_rewards() as u128;
+    }
+
+    // Legacy positions still count toward total_staked but are not reserved yet
+    let mut legacy_staked: u128 = 0;
+    for (address, account) in &legacy_accounts {
+        let stake = match LegacyStakeAccount::try_from_slice(&account.data) {
+            Ok(stake) if stake.is_initialized => stake,
+            Ok(_) => continue,
+            Err(e) => {
+                eprintln!("Skipping undecodable legacy account {}: {}", address, e);
+                continue;
+            }
+        };
+        if stake.amount > 0 {
+            positions += 1;
+        }
+        legacy_staked += stake.amount as u128;
+    }
+    summed_staked += legacy_staked;
+
+    let stake_vault_balance = fetch_token_amount(&client, &pool_config.stake_vault)?;
+    let reward_vault_balance = fetch_token_amount(&client, &pool_config.reward_vault)?;
+
+    println!("Pool config:          {}", pool_config_key);
+    println!(
+        "Stake accounts:       {} ({} legacy, {} active)",
+        stake_accounts.len() + legacy_accounts.len(),
+        legacy_accounts.len(),
+        positions
+    );
+    println!("Sum of stakes:        {}", summed_staked);
+    println!("PoolConfig staked:    {}", pool_config.total_staked);
+    println!("Legacy stakes:        {}", legacy_staked);
+    println!("PoolConfig legacy:    {}", pool_config.unmigrated_staked);
+    println!("Stake vault balance:  {}", stake_vault_balance);
+    println!("Sum of reservations:  {}", summed_reserved);
+    println!("PoolConfig liability: {}", pool_config.reward_liabilities);
+    println!("Reward vault balance: {}", reward_vault_balance);
+
+    let mut healthy = true;
+    if summed_staked != pool_config.total_staked as u128 {
+        println!("FAIL: sum of stake accounts does not equal total_staked");
+        healthy = false;
+    }
+    if legacy_staked != pool_config.unmigrated_staked as u128 {
+        println!("FAIL: sum of legacy stake accounts does not equal unmigrated_staked");
+        healthy = false;
+    }
+    if stake_vault_balance < pool_config.total_staked {
+        println!("FAIL: stake vault does not cover total_staked");
+        healthy = false;
+    }
+    if summed_reserved != pool_config.reward_liabilities as u128 {
+        println!("FAIL: sum of reward reservations does not equal reward_liabilities");
+        healthy = false;
+    }
+    if reward_vault_balance < pool_config.reward_liabilities {
+        println!("FAIL: reward vault does not cover reward_liabilities");
+        healthy = false;
+    }
+    if healthy {
+        println!("OK: all pool invariants hold");
+    }
+    Ok(healthy)
+}
+
+fn main() {
+    match run() {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(2);
+        }
+    }
+}